@@ -6,7 +6,7 @@ fn main() {
     let binary_name = env::var("CARGO_PKG_NAME").expect("CARGO_PKG_NAME not set");
 
     let target_dir = env::var("CARGO_TARGET_DIR")
-        .map_or_else(|_| "target".to_string(), |t| t);
+        .unwrap_or_else(|_| "target".to_string());
 
     let release_binary = Path::new(&target_dir).join("release").join(&binary_name);
     let destination = Path::new("..").join(&binary_name);
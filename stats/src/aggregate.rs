@@ -0,0 +1,747 @@
+//! The built-in metrics, aggregated in a single pass over the replay frames.
+//!
+//! Metrics only keep what ends up in the plot, so memory grows with the number of bots and the
+//! length of the resulting series, never with the raw log.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::map::{Map, MapObject};
+use crate::metric::{Metric, Options, Output};
+use crate::plot::{Color, Plot, Series, PALETTE};
+use crate::replay::{BotStatus, Frame};
+use crate::time::TimeBase;
+
+/// Run-length encoded samples, for values that rarely change between ticks.
+#[derive(Debug, Clone)]
+struct Runs<T> {
+    runs: Vec<(T, usize)>,
+}
+
+impl<T> Default for Runs<T> {
+    fn default() -> Self {
+        Runs { runs: Vec::new() }
+    }
+}
+
+impl<T: PartialEq + Clone> Runs<T> {
+    fn push(&mut self, value: &T) {
+        match self.runs.last_mut() {
+            Some((last, count)) if last == value => *count += 1,
+            _ => self.runs.push((value.clone(), 1)),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.runs
+            .iter()
+            .flat_map(|(value, count)| std::iter::repeat_n(value, *count))
+    }
+}
+
+/// Samples of one bot on its own time axis. When the bot is missing from frames between two
+/// samples, a NaN point splits the line instead of shifting later samples onto earlier ticks.
+#[derive(Debug, Clone)]
+struct Track<T> {
+    last_frame: Option<usize>,
+    times: Vec<f64>,
+    values: Runs<T>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Track {
+            last_frame: None,
+            times: Vec::new(),
+            values: Runs::default(),
+        }
+    }
+}
+
+impl<T: PartialEq + Clone> Track<T> {
+    /// Adds the sample of the `frame`-th frame passed to the metric.
+    fn push(&mut self, frame: usize, time: f64, value: &T) {
+        if self.last_frame.is_some_and(|last| last + 1 < frame) {
+            self.times.push(f64::NAN);
+        }
+
+        self.last_frame = Some(frame);
+        self.times.push(time);
+        self.values.push(value);
+    }
+
+    /// Samples as `(time, value)`, `None` at gaps.
+    fn samples(&self) -> impl Iterator<Item = Option<(f64, &T)>> {
+        let mut values = self.values.iter();
+        self.times.iter().map(move |time| {
+            if time.is_nan() {
+                None
+            } else {
+                Some((*time, values.next().expect("Sample without value")))
+            }
+        })
+    }
+
+    fn series(&self, name: String, y: impl Fn(&T) -> f64) -> Series {
+        let (x, y) = self
+            .samples()
+            .map(|sample| sample.map_or((f64::NAN, f64::NAN), |(time, value)| (time, y(value))))
+            .unzip();
+
+        Series { name, x, y }
+    }
+}
+
+pub(crate) fn selected(bot_ids: &[u16], bot_id: u16) -> bool {
+    bot_ids.is_empty() || bot_ids.contains(&bot_id)
+}
+
+/// An empty plot with the metric's title and labels.
+pub(crate) fn plot(metric: &dyn Metric) -> Plot {
+    let (x_label, y_label) = metric.labels();
+    Plot::new(&metric.title(), (&x_label, &y_label))
+}
+
+/// An empty plot with a time axis in the unit of `time`.
+fn time_plot(metric: &dyn Metric, time: &TimeBase) -> Plot {
+    let mut plot = plot(metric);
+    plot.x.clock = time.clock();
+    plot
+}
+
+/// Fixes the axes to the map and draws its walls, obstacles and stations, flipped upright.
+pub(crate) fn draw_map(plot: &mut Plot, map: &Map) {
+    plot.x.min = Some(0.0);
+    plot.x.max = Some(map.width);
+    plot.y.min = Some(0.0);
+    plot.y.max = Some(map.height);
+
+    let flip = |object: &MapObject| -> Vec<(f64, f64)> {
+        object
+            .corners()
+            .iter()
+            .map(|(x, y)| (*x, map.height - y))
+            .collect()
+    };
+
+    let walls = map
+        .obstacles
+        .iter()
+        .chain(&map.borders())
+        .cloned()
+        .collect::<Vec<_>>();
+    for (i, obstacle) in walls.iter().enumerate() {
+        let name = if i == 0 { "Obstacles" } else { "" };
+        plot.polygon(name, flip(obstacle), Color::GRAY);
+    }
+    plot.polygon("Station", flip(&map.station), Color::BLUE);
+    plot.polygon("Target Station", flip(&map.target_station), Color::RED);
+}
+
+fn push_time(times: &mut Vec<f64>, time: &TimeBase, frame: &Frame) -> bool {
+    if frame.bots.is_empty() {
+        return false;
+    }
+
+    times.push(time.value(frame.tick as f64));
+    true
+}
+
+#[derive(Default)]
+pub struct DataPerBot {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    frames: usize,
+    values: BTreeSet<u8>,
+    bots: BTreeMap<u16, Track<Vec<u8>>>,
+}
+
+impl DataPerBot {
+    pub fn new(options: &Options) -> Self {
+        DataPerBot {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for DataPerBot {
+    fn name(&self) -> &'static str {
+        "data-per-bot"
+    }
+
+    fn title(&self) -> String {
+        "Bot Data Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Presence of Data (0 or 1)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let time = self.time.value(frame.tick as f64);
+
+        for record in &frame.bots {
+            self.values.extend(&record.data);
+
+            if selected(&self.bot_ids, record.bot_id) {
+                self.bots
+                    .entry(record.bot_id)
+                    .or_default()
+                    .push(self.frames, time, &record.data);
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = time_plot(&*self, &self.time);
+
+        for (bot_id, track) in &self.bots {
+            for value in &self.values {
+                plot.line(
+                    track.series(format!("Bot {} - Data {}", bot_id, value), |data| {
+                        if data.contains(value) {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    }),
+                );
+            }
+        }
+
+        vec![Output::plot(self.name(), plot)]
+    }
+}
+
+#[derive(Default)]
+pub struct EnergyPerBot {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    frames: usize,
+    bots: BTreeMap<u16, Track<f64>>,
+}
+
+impl EnergyPerBot {
+    pub fn new(options: &Options) -> Self {
+        EnergyPerBot {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for EnergyPerBot {
+    fn name(&self) -> &'static str {
+        "energy-per-bot"
+    }
+
+    fn title(&self) -> String {
+        "Bot Energy Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Energy Per Bot (J)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let time = self.time.value(frame.tick as f64);
+
+        for record in &frame.bots {
+            if selected(&self.bot_ids, record.bot_id) {
+                self.bots
+                    .entry(record.bot_id)
+                    .or_default()
+                    .push(self.frames, time, &record.energy);
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = time_plot(&*self, &self.time);
+
+        for (bot_id, track) in &self.bots {
+            plot.line(track.series(format!("Bot {}", bot_id), |energy| *energy));
+        }
+
+        vec![Output::plot(self.name(), plot)]
+    }
+}
+
+#[derive(Default)]
+pub struct StatusPerBot {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    frames: usize,
+    unknown: BTreeSet<BotStatus>,
+    bots: BTreeMap<u16, Track<BotStatus>>,
+}
+
+impl StatusPerBot {
+    pub fn new(options: &Options) -> Self {
+        StatusPerBot {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for StatusPerBot {
+    fn name(&self) -> &'static str {
+        "status-per-bot"
+    }
+
+    fn title(&self) -> String {
+        "Bot Status Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Bot".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let time = self.time.value(frame.tick as f64);
+
+        for record in &frame.bots {
+            if let BotStatus::Unknown(_) = record.status {
+                self.unknown.insert(record.status.clone());
+            }
+
+            if selected(&self.bot_ids, record.bot_id) {
+                self.bots
+                    .entry(record.bot_id)
+                    .or_default()
+                    .push(self.frames, time, &record.status);
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = time_plot(&*self, &self.time);
+        plot.y.min = Some(-0.5);
+        plot.y.max = Some(self.bots.len() as f64 - 0.5);
+
+        // One row per bot, from the top.
+        let rows = self.bots.len() as f64 - 1.0;
+        plot.y.ticks = self
+            .bots
+            .keys()
+            .enumerate()
+            .map(|(row, bot_id)| (rows - row as f64, format!("Bot {}", bot_id)))
+            .collect();
+
+        // Segments of each status as rectangles; a status lasts until the next sample, gaps end it.
+        let mut segments: BTreeMap<&BotStatus, Vec<[(f64, f64); 2]>> = BTreeMap::new();
+        for (row, track) in self.bots.values().enumerate() {
+            let y = rows - row as f64;
+            let mut current: Option<(f64, f64, &BotStatus)> = None;
+
+            for sample in track.samples().chain(std::iter::once(None)) {
+                match (current, sample) {
+                    (Some((start, _, status)), Some((time, value))) if status == value => {
+                        current = Some((start, time, status));
+                    }
+                    (_, sample) => {
+                        if let Some((start, end, status)) = current {
+                            let end = sample.map_or(end, |(time, _)| time);
+                            segments
+                                .entry(status)
+                                .or_default()
+                                .push([(start, y - 0.4), (end, y + 0.4)]);
+                        }
+                        current = sample.map(|(time, value)| (time, time, value));
+                    }
+                }
+            }
+        }
+
+        for (level, status) in levels(&self.unknown).iter().enumerate() {
+            if let Some(segments) = segments.remove(status) {
+                plot.rectangles(&status.to_string(), segments, status_color(level, status));
+            }
+        }
+
+        vec![Output::plot(self.name(), plot)]
+    }
+}
+
+/// Known statuses followed by the unknown ones seen, in the order they are stacked and listed.
+pub(crate) fn levels(unknown: &BTreeSet<BotStatus>) -> Vec<BotStatus> {
+    BotStatus::KNOWN
+        .into_iter()
+        .chain(unknown.iter().cloned())
+        .collect()
+}
+
+/// Color of a status at `level` of [`levels`], the simulator draws bots depleted, in trophallaxis and
+/// transferring data in the same colors.
+pub(crate) fn status_color(level: usize, status: &BotStatus) -> Color {
+    match status {
+        BotStatus::Depleted => Color(17, 17, 17),
+        BotStatus::Trophallaxis => Color(240, 18, 191),
+        BotStatus::DataTransfer => Color(176, 13, 201),
+        BotStatus::Active => Color::GREEN,
+        BotStatus::ActiveAborting => Color::ORANGE,
+        BotStatus::Abort => Color::RED,
+        BotStatus::Unknown(_) => PALETTE[level % PALETTE.len()],
+    }
+}
+
+#[derive(Default)]
+pub struct StatusComposition {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    times: Vec<f64>,
+    unknown: BTreeSet<BotStatus>,
+    counts: Runs<BTreeMap<BotStatus, usize>>,
+}
+
+impl StatusComposition {
+    pub fn new(options: &Options) -> Self {
+        StatusComposition {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for StatusComposition {
+    fn name(&self) -> &'static str {
+        "status-composition"
+    }
+
+    fn title(&self) -> String {
+        "Swarm Status Composition Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Bots".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        if !push_time(&mut self.times, &self.time, frame) {
+            return;
+        }
+
+        let mut counts = BTreeMap::new();
+        for record in &frame.bots {
+            if let BotStatus::Unknown(_) = record.status {
+                self.unknown.insert(record.status.clone());
+            }
+
+            if selected(&self.bot_ids, record.bot_id) {
+                *counts.entry(record.status.clone()).or_insert(0) += 1;
+            }
+        }
+
+        self.counts.push(&counts);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = time_plot(&*self, &self.time);
+        plot.y.min = Some(0.0);
+
+        // Stacked areas, each between the running total below it and the total with its status.
+        let mut below = vec![0.0; self.times.len()];
+        for (level, status) in levels(&self.unknown).iter().enumerate() {
+            let above: Vec<f64> = self
+                .counts
+                .iter()
+                .zip(&below)
+                .map(|(counts, below)| below + counts.get(status).copied().unwrap_or(0) as f64)
+                .collect();
+            if above == below {
+                continue;
+            }
+
+            let mut points: Vec<(f64, f64)> = self
+                .times
+                .iter()
+                .copied()
+                .zip(above.iter().copied())
+                .collect();
+            points.extend(self.times.iter().copied().zip(below).rev());
+            plot.polygon(&status.to_string(), points, status_color(level, status));
+
+            below = above;
+        }
+
+        vec![Output::plot(self.name(), plot)]
+    }
+}
+
+#[derive(Default)]
+pub struct DataCumulative {
+    time: TimeBase,
+    times: Vec<f64>,
+    values: BTreeSet<u8>,
+    counts: Runs<BTreeMap<u8, usize>>,
+}
+
+impl DataCumulative {
+    pub fn new(options: &Options) -> Self {
+        DataCumulative {
+            time: options.time,
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for DataCumulative {
+    fn name(&self) -> &'static str {
+        "data-cumulative"
+    }
+
+    fn title(&self) -> String {
+        "Total Data In System (Bots) Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Total Data In System (Bots)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        if !push_time(&mut self.times, &self.time, frame) {
+            return;
+        }
+
+        let mut counts = BTreeMap::new();
+        for record in &frame.bots {
+            self.values.extend(&record.data);
+
+            for value in &record.data {
+                *counts.entry(*value).or_insert(0) += 1;
+            }
+        }
+
+        self.counts.push(&counts);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = time_plot(&*self, &self.time);
+
+        let series: Vec<Series> = self
+            .values
+            .iter()
+            .map(|value| Series {
+                name: value.to_string(),
+                x: self.times.clone(),
+                y: self
+                    .counts
+                    .iter()
+                    .map(|counts| counts.get(value).copied().unwrap_or(0) as f64)
+                    .collect(),
+            })
+            .collect();
+
+        for series in series {
+            plot.line(series);
+        }
+
+        vec![Output::plot(self.name(), plot)]
+    }
+}
+
+#[derive(Default)]
+pub struct EnergyCumulative {
+    time: TimeBase,
+    times: Vec<f64>,
+    energy: Vec<f64>,
+}
+
+impl EnergyCumulative {
+    pub fn new(options: &Options) -> Self {
+        EnergyCumulative {
+            time: options.time,
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for EnergyCumulative {
+    fn name(&self) -> &'static str {
+        "energy-cumulative"
+    }
+
+    fn title(&self) -> String {
+        "Total Energy In System (Bots) Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Total Energy In System (J)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        if push_time(&mut self.times, &self.time, frame) {
+            self.energy
+                .push(frame.bots.iter().map(|record| record.energy).sum());
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = time_plot(&*self, &self.time);
+
+        let name = self.name();
+        plot.line(Series {
+            name: "Total Energy".to_string(),
+            x: self.times,
+            y: self.energy,
+        });
+
+        vec![Output::plot(name, plot)]
+    }
+}
+
+#[derive(Default)]
+pub struct Locations {
+    bot_ids: Vec<u16>,
+    map: Option<Map>,
+    frames: usize,
+    max_y: Option<f64>,
+    bots: BTreeMap<u16, Track<(f64, f64)>>,
+}
+
+impl Locations {
+    pub fn new(options: &Options) -> Self {
+        Locations {
+            bot_ids: options.bot_ids.clone(),
+            map: options.map.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Metric for Locations {
+    fn name(&self) -> &'static str {
+        "locations"
+    }
+
+    fn title(&self) -> String {
+        "Locations of Bots Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        ("X Coordinate".to_string(), "Y Coordinate".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            self.max_y = Some(self.max_y.map_or(record.y, |max_y| max_y.max(record.y)));
+
+            self.bots.entry(record.bot_id).or_default().push(
+                self.frames,
+                frame.tick as f64,
+                &(record.x, record.y),
+            );
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = plot(&*self);
+
+        // The log has y pointing down, flip it so the plot looks like the simulation.
+        let max_y = match &self.map {
+            Some(map) => {
+                draw_map(&mut plot, map);
+                map.height
+            }
+            None => self.max_y.unwrap_or(0.0),
+        };
+
+        for (bot_id, track) in &self.bots {
+            let (x, y) = track
+                .samples()
+                .map(|sample| sample.map_or((f64::NAN, f64::NAN), |(_, (x, y))| (*x, max_y - y)))
+                .unzip();
+
+            plot.line(Series {
+                name: format!("Bot {}", bot_id),
+                x,
+                y,
+            });
+        }
+
+        vec![Output::plot(self.name(), plot)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_encode_repeated_values_once() {
+        let mut runs = Runs::default();
+        for value in [1, 1, 2, 2, 2, 1] {
+            runs.push(&value);
+        }
+
+        assert_eq!(runs.runs, vec![(1, 2), (2, 3), (1, 1)]);
+        assert_eq!(runs.iter().copied().collect::<Vec<_>>(), [1, 1, 2, 2, 2, 1]);
+    }
+
+    #[test]
+    fn track_without_gaps_has_no_nan() {
+        let mut track = Track::default();
+        for frame in 3..6 {
+            track.push(frame, frame as f64 * 2.0, &1.0);
+        }
+
+        assert_eq!(track.times, vec![6.0, 8.0, 10.0]);
+    }
+
+    #[test]
+    fn track_splits_at_gaps() {
+        let mut track = Track::default();
+        track.push(0, 0.0, &1.0);
+        track.push(1, 2.0, &1.0);
+        track.push(4, 8.0, &3.0);
+        track.push(5, 10.0, &4.0);
+        track.push(7, 14.0, &4.0);
+
+        let samples: Vec<_> = track.samples().collect();
+        assert_eq!(
+            samples,
+            vec![
+                Some((0.0, &1.0)),
+                Some((2.0, &1.0)),
+                None,
+                Some((8.0, &3.0)),
+                Some((10.0, &4.0)),
+                None,
+                Some((14.0, &4.0)),
+            ]
+        );
+
+        let series = track.series("Bot 0".to_string(), |value| value * 10.0);
+        assert_eq!(series.x.len(), 7);
+        assert_eq!(series.y.len(), 7);
+        for (i, (x, y)) in series.x.iter().zip(&series.y).enumerate() {
+            assert_eq!(x.is_nan(), i == 2 || i == 5);
+            assert_eq!(y.is_nan(), i == 2 || i == 5);
+        }
+        assert_eq!(series.y[3], 30.0);
+    }
+
+    #[test]
+    fn selects_every_bot_without_ids() {
+        assert!(selected(&[], 7));
+        assert!(selected(&[1, 7], 7));
+        assert!(!selected(&[1, 2], 7));
+    }
+}
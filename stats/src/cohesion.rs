@@ -0,0 +1,283 @@
+//! How spread out the swarm is over time: its centroid, radius of gyration, mean distance to the
+//! nearest neighbour and the number of clusters of bots within contact distance of each other.
+
+use crate::aggregate::selected;
+use crate::encounter::EncounterArgs;
+use crate::map::Map;
+use crate::metric::{Metric, Options, Output};
+use crate::network::Components;
+use crate::plot::{Plot, Series};
+use crate::replay::Frame;
+use crate::time::TimeBase;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    time: f64,
+    centroid: (f64, f64),
+    gyration: f64,
+    /// NaN with fewer than two bots.
+    nearest: f64,
+    clusters: usize,
+}
+
+/// Number of groups of bots connected through bots at most `radius` apart.
+fn clusters(bots: &[(u16, f64, f64)], radius: f64) -> usize {
+    let mut components = Components::new(&bots.iter().map(|(bot_id, _, _)| *bot_id).collect());
+    for (i, (a, ax, ay)) in bots.iter().enumerate() {
+        for (b, bx, by) in &bots[i + 1..] {
+            if (ax - bx).hypot(ay - by) <= radius {
+                components.join(*a, *b);
+            }
+        }
+    }
+
+    components.count
+}
+
+#[derive(Default)]
+pub struct Cohesion {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    map: Option<Map>,
+    radius: f64,
+    max_y: Option<f64>,
+    samples: Vec<Sample>,
+}
+
+impl Cohesion {
+    pub fn new(options: &Options) -> Self {
+        Cohesion {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            map: options.map.clone(),
+            radius: options.args::<EncounterArgs>().contact_distance,
+            ..Default::default()
+        }
+    }
+
+    fn time_plot(&self, title: &str, y_label: &str) -> Plot {
+        let mut plot = Plot::new(title, (&self.time.label(), y_label));
+        plot.x.clock = self.time.clock();
+        plot
+    }
+
+    fn series(&self, name: &str, y: impl Fn(&Sample) -> f64) -> Series {
+        Series {
+            name: name.to_string(),
+            x: self.samples.iter().map(|sample| sample.time).collect(),
+            y: self.samples.iter().map(y).collect(),
+        }
+    }
+}
+
+impl Metric for Cohesion {
+    fn name(&self) -> &'static str {
+        "cohesion"
+    }
+
+    fn title(&self) -> String {
+        "Swarm Dispersion Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Distance (px)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let bots: Vec<(u16, f64, f64)> = frame
+            .bots
+            .iter()
+            .filter(|record| selected(&self.bot_ids, record.bot_id))
+            .map(|record| (record.bot_id, record.x, record.y))
+            .collect();
+        if bots.is_empty() {
+            return;
+        }
+        let points: Vec<(f64, f64)> = bots.iter().map(|(_, x, y)| (*x, *y)).collect();
+        for (_, y) in &points {
+            self.max_y = Some(self.max_y.map_or(*y, |max_y| max_y.max(*y)));
+        }
+
+        let n = points.len() as f64;
+        let centroid = (
+            points.iter().map(|(x, _)| x).sum::<f64>() / n,
+            points.iter().map(|(_, y)| y).sum::<f64>() / n,
+        );
+        let gyration = (points
+            .iter()
+            .map(|(x, y)| (x - centroid.0).powi(2) + (y - centroid.1).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+
+        let nearest = if points.len() < 2 {
+            f64::NAN
+        } else {
+            points
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    points
+                        .iter()
+                        .enumerate()
+                        .filter(|(j, _)| *j != i)
+                        .map(|(_, b)| (a.0 - b.0).hypot(a.1 - b.1))
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .sum::<f64>()
+                / n
+        };
+
+        self.samples.push(Sample {
+            time: self.time.value(frame.tick as f64),
+            centroid,
+            gyration,
+            nearest,
+            clusters: clusters(&bots, self.radius),
+        });
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut dispersion = self.time_plot(&self.title(), &self.labels().1);
+        dispersion.y.min = Some(0.0);
+        dispersion.line(self.series("Radius of Gyration", |sample| sample.gyration));
+        dispersion.line(self.series("Mean Nearest-Neighbour Distance", |sample| sample.nearest));
+
+        // The log has y pointing down, flip it like the locations plot.
+        let max_y = match &self.map {
+            Some(map) => map.height,
+            None => self.max_y.unwrap_or(0.0),
+        };
+        let mut centroid = self.time_plot("Swarm Centroid Over Time", "Position (px)");
+        centroid.line(self.series("Centroid X", |sample| sample.centroid.0));
+        centroid.line(self.series("Centroid Y", |sample| max_y - sample.centroid.1));
+
+        let mut clusters = self.time_plot(
+            &format!("Clusters Within {} px Over Time", self.radius),
+            "Clusters",
+        );
+        clusters.y.min = Some(0.0);
+        clusters.legend = false;
+        clusters.line(self.series("Clusters", |sample| sample.clusters as f64));
+
+        vec![
+            Output::plot(self.name(), dispersion),
+            Output::plot(&format!("{}-centroid", self.name()), centroid),
+            Output::plot(&format!("{}-clusters", self.name()), clusters),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot::Layer;
+    use crate::replay::{BotStatus, ObjectType, Record};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn frame(tick: u64, positions: &[(f64, f64)]) -> Frame {
+        Frame {
+            tick,
+            bots: positions
+                .iter()
+                .enumerate()
+                .map(|(bot_id, (x, y))| Record {
+                    tick,
+                    bot_id: bot_id as u16,
+                    energy: 100.0,
+                    data: Vec::new(),
+                    x: *x,
+                    y: *y,
+                    vel_x: 0.0,
+                    vel_y: 0.0,
+                    rotation: 0.0,
+                    status: BotStatus::Active,
+                    color: [0.0; 4],
+                    r#type: ObjectType::Bot,
+                })
+                .collect(),
+            station: None,
+            target_station: None,
+        }
+    }
+
+    fn cohesion() -> Cohesion {
+        Cohesion {
+            radius: 5.0,
+            ..Default::default()
+        }
+    }
+
+    fn series<'a>(outputs: &'a [Output], name: &str) -> Vec<&'a Series> {
+        outputs
+            .iter()
+            .find_map(|output| match output {
+                Output::Plot { name: n, plot } if n == name => Some(plot),
+                _ => None,
+            })
+            .expect("No plot")
+            .layers
+            .iter()
+            .filter_map(|layer| match layer {
+                Layer::Line { series, .. } => Some(series),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_the_spread_of_the_swarm() {
+        let mut cohesion = cohesion();
+        cohesion.accumulate(&frame(0, &[(0.0, 0.0), (2.0, 0.0), (0.0, 2.0), (2.0, 2.0)]));
+        cohesion.accumulate(&frame(2, &[(0.0, 0.0), (6.0, 8.0)]));
+        cohesion.accumulate(&frame(4, &[(3.0, 4.0)]));
+
+        let [square, pair, single] = cohesion.samples[..] else {
+            panic!("Expected three samples");
+        };
+        assert_eq!(square.centroid, (1.0, 1.0));
+        assert_close(square.gyration, 2f64.sqrt());
+        assert_close(square.nearest, 2.0);
+        assert_eq!(pair.centroid, (3.0, 4.0));
+        assert_close(pair.gyration, 5.0);
+        assert_close(pair.nearest, 10.0);
+        assert_close(single.gyration, 0.0);
+        assert!(single.nearest.is_nan());
+    }
+
+    #[test]
+    fn counts_chains_of_bots_in_contact_as_one_cluster() {
+        // Ends of the chain are more than the radius apart, the last bot is just out of reach.
+        let bots = [
+            (0, 0.0, 0.0),
+            (1, 5.0, 0.0),
+            (2, 5.0, 5.0),
+            (3, 30.0, 0.0),
+            (4, 33.0, 4.0),
+            (5, 38.0, 4.1),
+        ];
+        assert_eq!(clusters(&bots, 5.0), 3);
+        assert_eq!(clusters(&bots[..3], 4.9), 3);
+        assert_eq!(clusters(&[], 5.0), 0);
+    }
+
+    #[test]
+    fn flips_the_centroid_upright() {
+        let mut cohesion = cohesion();
+        cohesion.accumulate(&frame(0, &[(0.0, 10.0), (4.0, 30.0)]));
+        cohesion.accumulate(&frame(2, &[(0.0, 40.0), (4.0, 60.0)]));
+
+        let outputs = Box::new(cohesion).finish();
+        let centroid = series(&outputs, "cohesion-centroid");
+        assert_eq!(centroid[0].y, vec![2.0, 2.0]);
+        assert_eq!(centroid[1].y, vec![40.0, 10.0]);
+    }
+}
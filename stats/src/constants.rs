@@ -0,0 +1,35 @@
+//! Constants of the simulator (`lib/constants.rb`) that analyses of its logs measure against.
+//!
+//! Keep these in sync with the Ruby side; nothing checks that they match.
+
+/// `Constants::Simulation::TICKS_PER_SECOND`.
+pub const TICKS_PER_SECOND: f64 = 60.0;
+
+/// `Constants::Bot::SIZE`, width and height of a bot in pixels.
+pub const BOT_SIZE: f64 = 3.0;
+
+/// `Constants::Bot::CM_PER_PX`, which is `20 / 3` in integer arithmetic.
+pub const CM_PER_PX: f64 = 6.0;
+
+/// `Constants::Bot::MAX_SPEED` in pixels per tick, 0.73 km/h.
+pub const MAX_SPEED: f64 = 0.73 * 100.0 / 3.6 / CM_PER_PX / TICKS_PER_SECOND;
+
+/// `Constants::Bot::MIN_SPEED` in pixels per tick, 0.18 km/h.
+pub const MIN_SPEED: f64 = 0.18 * 100.0 / 3.6 / CM_PER_PX / TICKS_PER_SECOND;
+
+/// `Constants::Bot::MAX_ENERGY_LEVEL` in joules.
+pub const MAX_ENERGY: f64 = 1080.0;
+
+/// `Constants::Bot::MIN_ENERGY_LEVEL` in joules, bots at or below it are depleted.
+pub const MIN_ENERGY: f64 = 72.0;
+
+/// Durations of the collision tasks in seconds.
+pub const CHARGE_DURATION: f64 = 16.0;
+pub const TROPHALLAXIS_DURATION: f64 = 16.0;
+pub const DATA_TRANSFER_DURATION: f64 = 6.0;
+
+/// `Constants::Bot::CURRENT_POWER_USAGE`: energy in joules a bot drains every second while moving at
+/// `speed` pixels per tick. Accelerating bots drain as if at [`MAX_SPEED`].
+pub fn power_usage(speed: f64) -> f64 {
+    0.542 * speed + 0.243
+}
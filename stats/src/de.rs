@@ -0,0 +1,34 @@
+use serde::Deserialize;
+
+pub(crate) fn data_field<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+
+    if s.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    s.split(',')
+        .map(|s| s.trim().parse::<u8>().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+pub(crate) fn color_field<'de, D>(deserializer: D) -> Result<[f64; 4], D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    let mut color = [0.0; 4];
+    let mut parts = s.split('|');
+
+    for channel in color.iter_mut() {
+        let part = parts
+            .next()
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid color: {}", s)))?;
+        *channel = part.trim().parse().map_err(serde::de::Error::custom)?;
+    }
+
+    Ok(color)
+}
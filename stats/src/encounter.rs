@@ -0,0 +1,533 @@
+//! Trophallaxis encounters reconstructed from the replay log.
+//!
+//! The log only has each bot's status, not who it is talking to. A bot-bot collision puts both bots
+//! through `trophallaxis` and `data_transfer` from the same tick on, so bots that enter those
+//! statuses at the same time and place are paired, unless the exchange could not have happened
+//! between them: trophallaxis never adds energy to the pair, and a bot only receives data its partner
+//! had. A bot that has no such partner is charging at, or exchanging data with, the nearest station
+//! when it is close enough to touch it, and with an unknown partner otherwise.
+
+use std::collections::BTreeMap;
+
+use crate::aggregate::{plot, selected};
+use crate::map::Map;
+use crate::metric::{Metric, Options, Output};
+use crate::plot::Series;
+use crate::replay::{BotStatus, Frame, Record};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+// Options of the encounter detection (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq)]
+pub struct EncounterArgs {
+    /// Largest distance in pixels between two bots in contact
+    #[arg(long, default_value_t = 10.0)]
+    pub contact_distance: f64,
+    /// Width and height in pixels of the stations, when there is no --map to take them from
+    #[arg(long, default_value_t = 20.0)]
+    pub station_size: f64,
+}
+
+impl Default for EncounterArgs {
+    fn default() -> Self {
+        EncounterArgs {
+            contact_distance: 10.0,
+            station_size: 20.0,
+        }
+    }
+}
+
+/// Whether a bot in `status` is exchanging energy or data with another object.
+pub fn in_contact(status: &BotStatus) -> bool {
+    matches!(status, BotStatus::Trophallaxis | BotStatus::DataTransfer)
+}
+
+/// One side of an encounter.
+#[derive(Debug, Clone)]
+pub struct Party {
+    pub bot_id: u16,
+    /// Energy and data of the last sample before and the first sample after the encounter.
+    pub energy_before: f64,
+    pub energy_after: f64,
+    pub data_before: Vec<u8>,
+    pub data_after: Vec<u8>,
+}
+
+impl Party {
+    /// Data values the bot did not have before the encounter.
+    pub fn received(&self) -> Vec<u8> {
+        self.data_after
+            .iter()
+            .filter(|value| !self.data_before.contains(value))
+            .copied()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Partner {
+    Bot(Party),
+    Station,
+    TargetStation,
+    /// No bot in contact and no station within reach.
+    Unknown,
+}
+
+impl Partner {
+    pub fn name(&self) -> String {
+        match self {
+            Partner::Bot(party) => format!("Bot {}", party.bot_id),
+            Partner::Station => "Station".to_string(),
+            Partner::TargetStation => "Target Station".to_string(),
+            Partner::Unknown => "Unknown".to_string(),
+        }
+    }
+
+    /// Kind of partner, without the bot id.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Partner::Bot(_) => "Bot",
+            Partner::Station => "Station",
+            Partner::TargetStation => "Target Station",
+            Partner::Unknown => "Unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Encounter {
+    pub start: u64,
+    /// Tick of the first sample after the encounter.
+    pub end: u64,
+    pub position: (f64, f64),
+    pub bot: Party,
+    pub partner: Partner,
+}
+
+impl Encounter {
+    pub fn duration(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn involves(&self, bot_ids: &[u16]) -> bool {
+        selected(bot_ids, self.bot.bot_id)
+            || matches!(&self.partner, Partner::Bot(party) if selected(bot_ids, party.bot_id))
+    }
+}
+
+/// Last sample of a bot.
+#[derive(Debug, Clone)]
+struct Sample {
+    frame: usize,
+    energy: f64,
+    data: Vec<u8>,
+}
+
+impl Sample {
+    fn new(frame: usize, record: &Record) -> Self {
+        Sample {
+            frame,
+            energy: record.energy,
+            data: record.data.clone(),
+        }
+    }
+}
+
+/// The contact statuses of a single bot, before it is paired.
+#[derive(Debug, Clone)]
+struct Episode {
+    frame: usize,
+    start: u64,
+    end: u64,
+    position: (f64, f64),
+    station: Partner,
+    party: Party,
+}
+
+/// Energy a pair of bots may seem to gain through rounding in the log.
+const ENERGY_TOLERANCE: f64 = 1.0;
+
+/// Whether trophallaxis and a data transfer between `a` and `b` explain how both changed.
+fn exchanged(a: &Party, b: &Party) -> bool {
+    let gained = a.energy_after + b.energy_after - a.energy_before - b.energy_before;
+
+    gained <= ENERGY_TOLERANCE
+        && a.received()
+            .iter()
+            .all(|value| b.data_before.contains(value))
+        && b.received()
+            .iter()
+            .all(|value| a.data_before.contains(value))
+}
+
+fn distance((x0, y0): (f64, f64), (x1, y1): (f64, f64)) -> f64 {
+    (x1 - x0).hypot(y1 - y0)
+}
+
+/// Finds encounters frame by frame, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    args: EncounterArgs,
+    /// Largest distance of a bot in contact with the station and the target station.
+    reach: (f64, f64),
+    frames: usize,
+    last: BTreeMap<u16, Sample>,
+    open: BTreeMap<u16, Episode>,
+    closed: Vec<Episode>,
+}
+
+impl Detector {
+    pub fn new(args: EncounterArgs, map: Option<&Map>) -> Self {
+        let reach = |size: f64| args.contact_distance + size;
+        Detector {
+            args,
+            reach: match map {
+                Some(map) => (
+                    reach(map.station.width.max(map.station.height)),
+                    reach(map.target_station.width.max(map.target_station.height)),
+                ),
+                None => (reach(args.station_size), reach(args.station_size)),
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn accumulate(&mut self, frame: &Frame) {
+        for record in &frame.bots {
+            let last = self
+                .last
+                .insert(record.bot_id, Sample::new(self.frames, record))
+                .filter(|last| last.frame + 1 == self.frames);
+
+            if let Some(mut episode) = self.open.remove(&record.bot_id) {
+                // A bot missing from the frames in between ends the episode at its last sample.
+                if last.is_some() {
+                    episode.end = frame.tick;
+                    episode.party.energy_after = record.energy;
+                    episode.party.data_after = record.data.clone();
+
+                    if in_contact(&record.status) {
+                        self.open.insert(record.bot_id, episode);
+                        continue;
+                    }
+                }
+                self.closed.push(episode);
+            }
+
+            if !in_contact(&record.status) {
+                continue;
+            }
+
+            let before = last.unwrap_or_else(|| Sample::new(self.frames, record));
+            let position = (record.x, record.y);
+            // Bots and stations are logged by their top left corner, so a bot touching a station
+            // can be as far from it as the station is wide.
+            let station = [
+                (&frame.station, self.reach.0, Partner::Station),
+                (&frame.target_station, self.reach.1, Partner::TargetStation),
+            ]
+            .into_iter()
+            .filter_map(|(station, reach, partner)| {
+                station
+                    .as_ref()
+                    .map(|station| (distance(position, (station.x, station.y)), partner))
+                    .filter(|(d, _)| *d <= reach)
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map_or(Partner::Unknown, |(_, partner)| partner);
+
+            self.open.insert(
+                record.bot_id,
+                Episode {
+                    frame: self.frames,
+                    start: frame.tick,
+                    end: frame.tick,
+                    position,
+                    station,
+                    party: Party {
+                        bot_id: record.bot_id,
+                        energy_before: before.energy,
+                        energy_after: record.energy,
+                        data_before: before.data,
+                        data_after: record.data.clone(),
+                    },
+                },
+            );
+        }
+
+        self.frames += 1;
+    }
+
+    /// Pairs up the episodes into encounters, ordered by start tick.
+    pub fn finish(self) -> Vec<Encounter> {
+        let mut episodes = self.closed;
+        episodes.extend(self.open.into_values());
+        episodes.sort_by_key(|episode| (episode.frame, episode.party.bot_id));
+
+        let mut paired = vec![false; episodes.len()];
+        let mut encounters = Vec::new();
+        for i in 0..episodes.len() {
+            if paired[i] {
+                continue;
+            }
+            paired[i] = true;
+
+            let episode = &episodes[i];
+            let partner = (i + 1..episodes.len())
+                .take_while(|j| episodes[*j].frame <= episode.frame + 1)
+                .filter(|j| !paired[*j] && episodes[*j].party.bot_id != episode.party.bot_id)
+                .map(|j| (distance(episode.position, episodes[j].position), j))
+                .filter(|(d, j)| {
+                    *d <= self.args.contact_distance
+                        && exchanged(&episode.party, &episodes[*j].party)
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b))
+                .map(|(_, j)| j);
+
+            encounters.push(match partner {
+                Some(j) => {
+                    paired[j] = true;
+                    let other = &episodes[j];
+                    Encounter {
+                        start: episode.start.min(other.start),
+                        end: episode.end.max(other.end),
+                        position: (
+                            (episode.position.0 + other.position.0) / 2.0,
+                            (episode.position.1 + other.position.1) / 2.0,
+                        ),
+                        bot: episode.party.clone(),
+                        partner: Partner::Bot(other.party.clone()),
+                    }
+                }
+                None => Encounter {
+                    start: episode.start,
+                    end: episode.end,
+                    position: episode.position,
+                    bot: episode.party.clone(),
+                    partner: episode.station.clone(),
+                },
+            });
+        }
+
+        encounters
+    }
+}
+
+fn data(values: &[u8]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub struct Encounters {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    detector: Detector,
+}
+
+impl Encounters {
+    pub fn new(options: &Options) -> Self {
+        Encounters {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            detector: Detector::new(options.args(), options.map.as_ref()),
+        }
+    }
+
+    fn table(&self, encounters: &[Encounter]) -> Table {
+        let unit = self.time.unit.name();
+        let start = format!("Start ({})", unit);
+        let duration = format!("Duration ({})", unit);
+        let mut table = Table::new(
+            "Encounters",
+            &[
+                &start,
+                &duration,
+                "X",
+                "Y",
+                "Bot",
+                "Partner",
+                "Energy Before",
+                "Energy After",
+                "Partner Energy Before",
+                "Partner Energy After",
+                "Data Received",
+                "Partner Data Received",
+            ],
+        );
+
+        for encounter in encounters {
+            let bot = &encounter.bot;
+            let mut row = vec![
+                self.time.format(encounter.start as f64),
+                self.time.format(encounter.duration() as f64),
+                format!("{:.1}", encounter.position.0),
+                format!("{:.1}", encounter.position.1),
+                bot.bot_id.to_string(),
+                encounter.partner.name(),
+                format!("{:.2}", bot.energy_before),
+                format!("{:.2}", bot.energy_after),
+            ];
+            match &encounter.partner {
+                Partner::Bot(other) => row.extend([
+                    format!("{:.2}", other.energy_before),
+                    format!("{:.2}", other.energy_after),
+                    data(&bot.received()),
+                    data(&other.received()),
+                ]),
+                _ => row.extend([
+                    String::new(),
+                    String::new(),
+                    data(&bot.received()),
+                    String::new(),
+                ]),
+            }
+            table.push(row);
+        }
+
+        table
+    }
+}
+
+impl Metric for Encounters {
+    fn name(&self) -> &'static str {
+        "encounters"
+    }
+
+    fn title(&self) -> String {
+        "Encounters Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Encounters".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        self.detector.accumulate(frame);
+    }
+
+    fn finish(mut self: Box<Self>) -> Vec<Output> {
+        let encounters: Vec<Encounter> = std::mem::take(&mut self.detector)
+            .finish()
+            .into_iter()
+            .filter(|encounter| encounter.involves(&self.bot_ids))
+            .collect();
+
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+
+        // Running count of encounters per kind of partner, stepping up at each start.
+        for kind in ["Bot", "Station", "Target Station", "Unknown"] {
+            let mut series = Series {
+                name: format!("{} Encounters", kind),
+                x: Vec::new(),
+                y: Vec::new(),
+            };
+            let starts = encounters
+                .iter()
+                .filter(|encounter| encounter.partner.kind() == kind)
+                .map(|encounter| self.time.value(encounter.start as f64));
+            for (count, start) in starts.enumerate() {
+                series.x.extend([start, start]);
+                series.y.extend([count as f64, count as f64 + 1.0]);
+            }
+
+            if !series.x.is_empty() {
+                plot.line(series);
+            }
+        }
+
+        vec![
+            Output::table(self.name(), self.table(&encounters)),
+            Output::plot(self.name(), plot),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ObjectType;
+
+    fn record(bot_id: u16, (x, y): (f64, f64), status: BotStatus, r#type: ObjectType) -> Record {
+        Record {
+            tick: 0,
+            bot_id,
+            energy: 100.0,
+            data: Vec::new(),
+            x,
+            y,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            rotation: 0.0,
+            status,
+            color: [0.0; 4],
+            r#type,
+        }
+    }
+
+    /// Frames at ticks 0, 2, ... with the bots at `positions`, in contact in the middle frame.
+    fn encounters(positions: &[(f64, f64)], station: (f64, f64)) -> Vec<Encounter> {
+        let mut detector = Detector::new(EncounterArgs::default(), None);
+        for (i, status) in [
+            BotStatus::Active,
+            BotStatus::Trophallaxis,
+            BotStatus::Active,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            detector.accumulate(&Frame {
+                tick: i as u64 * 2,
+                bots: positions
+                    .iter()
+                    .enumerate()
+                    .map(|(bot_id, position)| {
+                        record(bot_id as u16, *position, status.clone(), ObjectType::Bot)
+                    })
+                    .collect(),
+                station: Some(record(0, station, BotStatus::Active, ObjectType::Station)),
+                target_station: Some(record(
+                    0,
+                    (400.0, 300.0),
+                    BotStatus::Active,
+                    ObjectType::TargetStation,
+                )),
+            });
+        }
+
+        detector.finish()
+    }
+
+    #[test]
+    fn pairs_bots_in_contact() {
+        let encounters = encounters(&[(100.0, 100.0), (104.0, 100.0)], (40.0, 140.0));
+
+        assert_eq!(encounters.len(), 1);
+        assert_eq!(encounters[0].start, 2);
+        assert_eq!(encounters[0].end, 4);
+        assert_eq!(encounters[0].partner.name(), "Bot 1");
+    }
+
+    #[test]
+    fn credits_a_station_within_reach() {
+        // Right of the station, as far from its corner as the station is wide plus a gap.
+        let encounters = encounters(&[(65.0, 150.0)], (40.0, 140.0));
+
+        assert_eq!(encounters.len(), 1);
+        assert_eq!(encounters[0].partner.kind(), "Station");
+    }
+
+    #[test]
+    fn leaves_distant_partners_unknown() {
+        let encounters = encounters(&[(100.0, 100.0), (200.0, 100.0)], (40.0, 140.0));
+
+        assert_eq!(encounters.len(), 2);
+        assert!(encounters
+            .iter()
+            .all(|encounter| encounter.partner.kind() == "Unknown"));
+    }
+}
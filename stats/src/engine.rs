@@ -0,0 +1,213 @@
+//! Reads a replay log once and fans every frame out to the registered metrics.
+//!
+//! Frames are parsed on the calling thread while the metrics are spread over worker threads,
+//! so parsing and aggregation overlap and no metric regroups the log on its own.
+
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::metric::{Metric, Output};
+use crate::replay::Frame;
+
+/// Frames buffered per worker before the reader blocks, which bounds memory when a worker lags behind.
+const FRAME_BUFFER: usize = 256;
+
+pub struct Engine {
+    workers: usize,
+    metrics: Vec<Box<dyn Metric>>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_workers(workers)
+    }
+
+    pub fn with_workers(workers: usize) -> Self {
+        Engine {
+            workers: workers.max(1),
+            metrics: Vec::new(),
+        }
+    }
+
+    /// Registers a metric and returns the index of its outputs in the result of [`Engine::run`].
+    pub fn register(&mut self, metric: Box<dyn Metric>) -> usize {
+        self.metrics.push(metric);
+        self.metrics.len() - 1
+    }
+
+    pub fn run<I>(self, frames: I) -> Result<Vec<Vec<Output>>, csv::Error>
+    where
+        I: IntoIterator<Item = Result<Frame, csv::Error>>,
+    {
+        let workers = self.workers.min(self.metrics.len()).max(1);
+
+        let mut buckets: Vec<Vec<(usize, Box<dyn Metric>)>> =
+            (0..workers).map(|_| Vec::new()).collect();
+        for (index, metric) in self.metrics.into_iter().enumerate() {
+            buckets[index % workers].push((index, metric));
+        }
+
+        thread::scope(|scope| {
+            let (senders, handles): (Vec<SyncSender<Arc<Frame>>>, Vec<_>) = buckets
+                .into_iter()
+                .map(|bucket| {
+                    let (sender, receiver) = mpsc::sync_channel(FRAME_BUFFER);
+                    (sender, scope.spawn(move || work(bucket, receiver)))
+                })
+                .unzip();
+
+            let mut result = Ok(());
+            for frame in frames {
+                match frame {
+                    Ok(frame) => {
+                        let frame = Arc::new(frame);
+                        for sender in &senders {
+                            sender
+                                .send(Arc::clone(&frame))
+                                .expect("Aggregation worker stopped");
+                        }
+                    }
+                    Err(err) => {
+                        result = Err(err);
+                        break;
+                    }
+                }
+            }
+            drop(senders);
+
+            let mut outputs: Vec<(usize, Vec<Output>)> = handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("Aggregation worker panicked"))
+                .collect();
+            outputs.sort_by_key(|(index, _)| *index);
+
+            result.map(|_| outputs.into_iter().map(|(_, outputs)| outputs).collect())
+        })
+    }
+}
+
+fn work(
+    mut metrics: Vec<(usize, Box<dyn Metric>)>,
+    frames: Receiver<Arc<Frame>>,
+) -> Vec<(usize, Vec<Output>)> {
+    for frame in frames {
+        for (_, metric) in metrics.iter_mut() {
+            metric.accumulate(&frame);
+        }
+    }
+
+    metrics
+        .into_iter()
+        .map(|(index, metric)| (index, metric.finish()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::Table;
+
+    /// Records the ticks of the frames it sees.
+    struct Ticks {
+        name: &'static str,
+        ticks: Vec<u64>,
+    }
+
+    impl Metric for Ticks {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn accumulate(&mut self, frame: &Frame) {
+            self.ticks.push(frame.tick);
+        }
+
+        fn finish(self: Box<Self>) -> Vec<Output> {
+            let mut table = Table::new(self.name, &["Tick"]);
+            for tick in &self.ticks {
+                table.push(vec![tick.to_string()]);
+            }
+            vec![Output::table(self.name, table)]
+        }
+    }
+
+    const NAMES: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+    fn frame(tick: u64) -> Result<Frame, csv::Error> {
+        Ok(Frame {
+            tick,
+            bots: Vec::new(),
+            station: None,
+            target_station: None,
+        })
+    }
+
+    fn engine(workers: usize) -> Engine {
+        let mut engine = Engine::with_workers(workers);
+        for (i, name) in NAMES.iter().enumerate() {
+            let index = engine.register(Box::new(Ticks {
+                name,
+                ticks: Vec::new(),
+            }));
+            assert_eq!(index, i);
+        }
+        engine
+    }
+
+    /// Name and ticks of the table of each metric.
+    fn tables(outputs: Vec<Vec<Output>>) -> Vec<(String, Vec<String>)> {
+        outputs
+            .into_iter()
+            .flatten()
+            .map(|output| match output {
+                Output::Table { name, table } => (name, table.rows.into_iter().flatten().collect()),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_metric_sees_every_frame_in_order() {
+        for workers in [0, 1, 2, 8] {
+            let outputs = engine(workers).run((0..100).map(frame)).unwrap();
+
+            let ticks: Vec<String> = (0..100).map(|tick| tick.to_string()).collect();
+            let tables = tables(outputs);
+            assert_eq!(tables.len(), NAMES.len());
+            for ((name, rows), expected) in tables.into_iter().zip(NAMES) {
+                assert_eq!(name, expected, "{} workers", workers);
+                assert_eq!(rows, ticks, "{} workers", workers);
+            }
+        }
+    }
+
+    #[test]
+    fn runs_without_metrics() {
+        assert!(Engine::with_workers(4)
+            .run((0..3).map(frame))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let error = csv::Reader::from_reader("a,b\n1\n".as_bytes())
+            .records()
+            .find_map(Result::err)
+            .unwrap();
+        let frames = (0..3)
+            .map(frame)
+            .chain([Err(error)])
+            .chain((3..5).map(frame));
+
+        assert!(engine(2).run(frames).is_err());
+    }
+}
@@ -1,66 +1,34 @@
-use clap::Parser;
+use capbot_stats::constants::MAX_ENERGY;
+use capbot_stats::experiment::{self, Bot, Event, TweakValue};
+use capbot_stats::time::{TimeBase, TimeUnit};
+use clap::{CommandFactory, FromArgMatches, Parser};
 use prettytable::{Table, Row, Cell};
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     #[arg(short, long)]
     input: String,
+    #[command(flatten)]
+    time: TimeBase,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(untagged)]
-enum TweakValue {
-    Single(f64),
-    Range([f64; 2]),
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "message", rename_all = "snake_case")]
-enum Event {
-    TweakedConstants {
-        tweaks: HashMap<String, TweakValue>,
-    },
-    StopConditionReached {
-        iteration: u32,
-        tick: u32,
-        bots: Vec<Bot>,
-    },
-    ExperimentFinished {
-        duration: f64,
-    },
-    AllDepleted {},
-    BotKilled {
-        id: u32,
-        iteration: u32,
-        tick: u32,
-    },
-}
+type PairedEvent = (u32, u32, HashMap<String, TweakValue>, Vec<Bot>);
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Data {
-    id: u32,
-    r#type: String,
-    content: u32,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Bot {
-    id: u32,
-//     data: Vec<Data>,
-    energy: f64,
+fn duration(time: &TimeBase, ticks: u32) -> String {
+    match time.unit {
+        TimeUnit::Ticks => format!("{} ticks", ticks),
+        TimeUnit::MinutesSeconds => format!("{} ticks ({})", ticks, time.format(ticks as f64)),
+        unit => format!("{} ticks ({} {})", ticks, time.format(ticks as f64), unit.name()),
+    }
 }
 
-const MAX_ENERGY_PER_BOT: f64 = 1080.0;
-
 fn main() {
-    let args = Args::parse();
+    let command = Args::command().mut_arg("unit", |arg| arg.default_value("mm:ss"));
+    let args = Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit());
 
-    let json_data = fs::read_to_string(&args.input).expect("Unable to read file");
-    let events: Vec<Event> = serde_json::from_str(&json_data).expect("Invalid JSON format");
+    let events = experiment::parse_events(&args.input).expect("Unable to read events");
 
     let mut last_tweaks = HashMap::new();
     let mut paired_events = vec![];
@@ -75,6 +43,7 @@ fn main() {
                 iteration,
                 tick,
                 bots,
+                ..
             } => {
                 paired_events.push((tick, iteration, last_tweaks.clone(), bots));
             }
@@ -110,7 +79,7 @@ fn main() {
     let ci_95_lower = average_energy - 1.96 * standard_error;
     let ci_95_upper = average_energy + 1.96 * standard_error;
 
-    let max_possible_per_bot = MAX_ENERGY_PER_BOT;
+    let max_possible_per_bot = MAX_ENERGY;
     let average_bots_per_iteration = paired_events
         .iter()
         .map(|(_, _, _, bots)| bots.len())
@@ -162,7 +131,7 @@ fn main() {
     paired_events.sort_by_key(|&(tick, _, _, _)| std::cmp::Reverse(tick));
     let worst = paired_events.iter().take(4).cloned().collect::<Vec<_>>();
 
-    display_combined_tweaks_table(&best, &worst);
+    display_combined_tweaks_table(&best, &worst, &args.time);
     analyze_good_bad_parameters(&best, &worst);
 
     println!();
@@ -184,7 +153,7 @@ fn main() {
             .sum::<u32>()
             / paired_events.len() as u32;
 
-    println!("Average duration: {} ticks ({}:{})", average_duration, average_duration / 3600, average_duration % 3600 / 60);
+    println!("Average duration: {}", duration(&args.time, average_duration));
 
     let found_iterations = paired_events
         .iter()
@@ -201,12 +170,13 @@ fn main() {
         .sum::<u32>()
         / found_iterations.count() as u32;
 
-    println!("Average duration (found): {} ticks ({}:{})", average_duration_found, average_duration_found / 3600, average_duration_found % 3600 / 60);
+    println!("Average duration (found): {}", duration(&args.time, average_duration_found));
 }
 
 fn display_combined_tweaks_table(
-    best: &[(u32, u32, HashMap<String, TweakValue>, Vec<Bot>)],
-    worst: &[(u32, u32, HashMap<String, TweakValue>, Vec<Bot>)],
+    best: &[PairedEvent],
+    worst: &[PairedEvent],
+    time: &TimeBase,
 ) {
 
     let mut table = Table::new();
@@ -264,26 +234,24 @@ fn display_combined_tweaks_table(
     }
     table.add_row(Row::new(ticks_row));
 
-    let mut minutes_row = vec![Cell::new("Duration (minutes)")];
-    for (tick, _, _, _) in best {
-        let minutes = *tick / 3600;
-        let seconds = *tick % 3600 / 60;
-        minutes_row.push(Cell::new(&format!("{}:{}", minutes, seconds)));
-    }
-    minutes_row.push(Cell::new("|"));
-    for (tick, _, _, _) in worst {
-        let minutes = *tick / 3600;
-        let seconds = *tick % 3600 / 60;
-        minutes_row.push(Cell::new(&format!("{}:{}", minutes, seconds)));
+    if time.unit != TimeUnit::Ticks {
+        let mut time_row = vec![Cell::new(&format!("Duration ({})", time.unit.name()))];
+        for (tick, _, _, _) in best {
+            time_row.push(Cell::new(&time.format(*tick as f64)));
+        }
+        time_row.push(Cell::new("|"));
+        for (tick, _, _, _) in worst {
+            time_row.push(Cell::new(&time.format(*tick as f64)));
+        }
+        table.add_row(Row::new(time_row));
     }
-    table.add_row(Row::new(minutes_row));
 
     table.printstd();
 }
 
 fn analyze_good_bad_parameters(
-    best: &[(u32, u32, HashMap<String, TweakValue>, Vec<Bot>)],
-    worst: &[(u32, u32, HashMap<String, TweakValue>, Vec<Bot>)],
+    best: &[PairedEvent],
+    worst: &[PairedEvent],
 ) {
     let mut good_values: HashMap<String, Vec<f64>> = HashMap::new();
     let mut bad_values: HashMap<String, Vec<f64>> = HashMap::new();
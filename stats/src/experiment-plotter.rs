@@ -1,10 +1,8 @@
-use clap::Parser;
-use gnuplot::{
-    AutoOption, AxesCommon, Caption, Color, Coordinate, Figure, LabelOption, MarginSide,
-    PlotOption::LineWidth,
-};
-use serde::Deserialize;
-use std::{collections::HashMap, path::Path, u64};
+use capbot_stats::experiment;
+use capbot_stats::plot::{Color, Layer, Plot, PlotArgs, Series, Theme};
+use capbot_stats::time::{TimeBase, TimeUnit};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::collections::HashMap;
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
@@ -14,14 +12,12 @@ struct Args {
 
     #[arg(long, required = true, num_args = 1.., value_delimiter = ',')]
     names: Vec<String>,
-}
 
-#[derive(Debug, Deserialize)]
-struct Record {
-    //    iteration: u64,
-    tick: u64,
-    //    #[serde(deserialize_with = "parse_data_field")]
-    //    data: Vec<u8>,
+    #[command(flatten)]
+    time: TimeBase,
+
+    #[command(flatten)]
+    plot: PlotArgs,
 }
 
 fn moving_average(data: &[f64], window_size: usize) -> Vec<f64> {
@@ -35,47 +31,36 @@ fn moving_average(data: &[f64], window_size: usize) -> Vec<f64> {
     avg
 }
 
-fn parse_data_field<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let s: String = Deserialize::deserialize(deserializer)?;
-    if s.trim().is_empty() {
-        return Ok(Vec::new());
-    }
-    s.split(',')
-        .map(|s| s.trim().parse::<u8>().map_err(serde::de::Error::custom))
-        .collect()
-}
-
-fn parse_csv(path: &str) -> Result<Vec<Record>, Box<dyn std::error::Error>> {
-    let file = std::fs::File::open(path)?;
-    let mut rdr = csv::Reader::from_reader(file);
-    let mut records = Vec::new();
-    for result in rdr.deserialize() {
-        let record: Record = result?;
-        records.push(record);
-    }
-    Ok(records)
-}
-
-fn ticks_to_minutes(ticks: u64) -> u64 {
-    (ticks as f64 / (60.0 * 60.0)) as u64
+/// Whole minute a tick falls in; results are always counted per minute, `--unit` only changes
+/// how the x axis is labelled.
+fn minute_bin(ticks: u64, time: &TimeBase) -> u64 {
+    (time.seconds(ticks as f64) / 60.0) as u64
 }
 
-fn get_filename_without_ext(path: &str) -> String {
-    Path::new(path)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("unknown")
-        .to_string()
+fn time_label(time: &TimeBase) -> String {
+    let unit = match time.unit {
+        TimeUnit::Ticks => "ticks",
+        TimeUnit::Seconds => "seconden",
+        TimeUnit::Minutes => "minuten",
+        TimeUnit::MinutesSeconds => "mm:ss",
+    };
+    format!("Tijd ({})", unit)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    let command = Args::command().mut_arg("unit", |arg| arg.default_value("minutes"));
+    let args = Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit());
+    let renderer = args.plot.renderer()?;
 
     let colors = [
-        "red", "blue", "green", "purple", "orange", "black", "brown", "cyan",
+        Color::RED,
+        Color::BLUE,
+        Color::GREEN,
+        Color::PURPLE,
+        Color::ORANGE,
+        Color::BLACK,
+        Color::BROWN,
+        Color::CYAN,
     ];
 
     let mut all_times_counts: Vec<(Vec<u64>, Vec<f64>, String)> = Vec::new();
@@ -84,11 +69,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let display_name = args.names[idx].clone();
         println!("Processing file: {} ({})", log_file, display_name);
 
-        let records = parse_csv(log_file).expect(&format!("Error reading records from {}", log_file));
+        let records = experiment::parse_csv(log_file)
+            .unwrap_or_else(|_| panic!("Error reading records from {}", log_file));
 
         let mut time_counts: HashMap<u64, u32> = HashMap::new();
         for record in &records {
-            let minutes = ticks_to_minutes(record.tick);
+            let minutes = minute_bin(record.tick as u64, &args.time);
             *time_counts.entry(minutes).or_insert(0) += 1;
         }
 
@@ -105,51 +91,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         all_times_counts.push((times, counts, display_name));
     }
 
-    let mut fg = Figure::new();
-    let mut axes = fg
-        .axes2d()
-        .set_margins(&vec![
-            MarginSide::MarginLeft(0.08),
-            MarginSide::MarginRight(0.95),
-            MarginSide::MarginBottom(0.15),
-            MarginSide::MarginTop(0.85),
-        ])
-        .set_x_range(AutoOption::Fix(0.0), AutoOption::Auto)
-        .set_y_range(AutoOption::Fix(0.0), AutoOption::Auto)
-        .set_x_label(
-            "Tijd (minuten)",
-            &vec![
-                LabelOption::Font("Arial", 50.0),
-                LabelOption::TextOffset(0.0, -3.0),
-            ],
-        )
-        .set_y_label(
-            "Simulaties (%)",
-            &vec![
-                LabelOption::Font("Arial", 50.0),
-                LabelOption::TextOffset(-5.0, 0.0),
-            ],
-        )
-        .set_x_ticks(
-            Some((AutoOption::Auto, 0)),
-            &[],
-            &vec![LabelOption::Font("Arial", 20.0)],
-        )
-        .set_y_ticks(
-            Some((AutoOption::Auto, 0)),
-            &[],
-            &vec![
-                LabelOption::Font("Arial", 20.0),
-                LabelOption::Rotate(90.0),
-                LabelOption::TextOffset(0.0, 1.0),
-            ],
-        )
-        .set_legend(
-            Coordinate::Graph(0.98),
-            Coordinate::Graph(0.98),
-            &vec![],
-            &vec![LabelOption::Font("Arial", 40.0)],
-        );
+    let mut plot = Plot::new("", (&time_label(&args.time), "Simulaties (%)"));
+    plot.x.min = Some(0.0);
+    plot.y.min = Some(0.0);
+    plot.x.clock = args.time.clock();
+    plot.theme = Theme {
+        label_size: 50.0,
+        tick_size: 20.0,
+        legend_size: 40.0,
+        ..Default::default()
+    };
 
     for (idx, (times, counts, name)) in all_times_counts.iter().enumerate() {
         let mut times_f64: Vec<f64> = times.iter().map(|&x| x as f64).collect();
@@ -174,31 +125,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         let color = colors[idx % colors.len()];
 
-//         // plot original line (thin)
-//         axes = axes.lines(
-//             &times_f64,
-//             &counts_f64,
-//             &vec![
-//                 Caption(format!("{} (raw)", name).as_str()),
-//                 LineWidth(2.0),
-//                 Color(color),
-//             ],
-//         );
-
         // plot moving average line (thick)
-        axes = axes.lines(
-            &times_f64,
-            &ma_counts,
-            &vec![
-                Caption(format!("{}", name).as_str()),
-                LineWidth(8.0),
-                Color(color),
-            ],
-        );
+        plot.layers.push(Layer::Line {
+            series: Series {
+                name: name.clone(),
+                x: times_f64
+                    .iter()
+                    .map(|minutes| args.time.value(args.time.ticks(minutes * 60.0)))
+                    .collect(),
+                y: ma_counts,
+            },
+            color: Some(color),
+            width: 8.0,
+        });
     }
 
-
-    let _ = fg.show();
+    renderer.render("experiment-plotter", &plot)?;
 
     println!("Plot completed with {} datasets", all_times_counts.len());
 
@@ -0,0 +1,83 @@
+//! Experiment output written by `Logger::ExperimentLogger` (`experiments/<name>/`).
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::replay::ObjectType;
+
+/// A row of `data.csv`, logged for the station and the target station whenever a stop condition is reached.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Record {
+    pub iteration: u32,
+    pub tick: u32,
+    #[serde(deserialize_with = "crate::de::data_field")]
+    pub data: Vec<u8>,
+    pub r#type: ObjectType,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TweakValue {
+    Single(f64),
+    Range([f64; 2]),
+}
+
+/// A message of `info.json`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "message", rename_all = "snake_case")]
+pub enum Event {
+    TweakedConstants {
+        tweaks: HashMap<String, TweakValue>,
+    },
+    StopConditionReached {
+        iteration: u32,
+        tick: u32,
+        bots: Vec<Bot>,
+        #[serde(default, deserialize_with = "crate::de::data_field")]
+        station: Vec<u8>,
+        #[serde(default, deserialize_with = "crate::de::data_field")]
+        target_station: Vec<u8>,
+    },
+    ExperimentFinished {
+        duration: f64,
+    },
+    AllDepleted {},
+    BotKilled {
+        id: u32,
+        iteration: u32,
+        tick: u32,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Data {
+    pub id: u32,
+    pub r#type: String,
+    pub content: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Bot {
+    pub id: u32,
+    #[serde(default)]
+    pub data: Vec<Data>,
+    pub energy: f64,
+}
+
+pub fn parse_csv(path: &str) -> Result<Vec<Record>, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path)?;
+    let mut rdr = csv::Reader::from_reader(file);
+    let mut records = Vec::new();
+
+    for result in rdr.deserialize() {
+        let record: Record = result?;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+pub fn parse_events(path: &str) -> Result<Vec<Event>, Box<dyn std::error::Error>> {
+    let json = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
@@ -0,0 +1,475 @@
+//! How far bots drive and how much of the map they get to see.
+//!
+//! The map is split into the square cells of the occupancy heatmap, and a cell counts as visited
+//! once a bot was logged in it. Cells are weighted by the part of them not covered by obstacles or
+//! the border walls, so coverage is the fraction of the free area of the map that was visited.
+//! Without a map, the free area is taken to be every cell within the bounds of the visited ones.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::aggregate::{plot, selected};
+use crate::constants::CM_PER_PX;
+use crate::map::{Map, MapObject};
+use crate::metric::{Metric, Options, Output};
+use crate::occupancy::{cell, grid_bounds, Cell, OccupancyArgs};
+use crate::plot::{Color, Layer, Plot, Series};
+use crate::replay::Frame;
+use crate::table::Table;
+use crate::time::TimeBase;
+
+/// Points per side of a cell sampled to measure how much of it is free.
+const SAMPLES: usize = 10;
+
+#[derive(Debug, Clone, Default)]
+struct Odometer {
+    last: Option<(usize, f64, f64)>,
+    /// Distance in pixels since the bot's first sample.
+    distance: f64,
+    /// Times and distances, NaN at gaps.
+    times: Vec<f64>,
+    distances: Vec<f64>,
+}
+
+#[derive(Default)]
+pub struct Distance {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    frames: usize,
+    bots: BTreeMap<u16, Odometer>,
+    /// Times and distance of the whole swarm.
+    times: Vec<f64>,
+    totals: Vec<f64>,
+}
+
+impl Distance {
+    pub fn new(options: &Options) -> Self {
+        Distance {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+
+    fn summary(&self) -> Table {
+        let mut table = Table::new(
+            "Distance Travelled",
+            &["Bot", "Distance (px)", "Distance (cm)"],
+        );
+
+        let rows = self
+            .bots
+            .iter()
+            .map(|(bot_id, bot)| (bot_id.to_string(), bot.distance))
+            .chain(std::iter::once((
+                "Swarm".to_string(),
+                self.bots.values().map(|bot| bot.distance).sum(),
+            )));
+        for (bot, distance) in rows {
+            table.push(vec![
+                bot,
+                format!("{:.1}", distance),
+                format!("{:.1}", distance * CM_PER_PX),
+            ]);
+        }
+
+        table
+    }
+}
+
+impl Metric for Distance {
+    fn name(&self) -> &'static str {
+        "distance"
+    }
+
+    fn title(&self) -> String {
+        "Distance Travelled per Bot Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Distance (cm)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let time = self.time.value(frame.tick as f64);
+        let mut seen = false;
+
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+            seen = true;
+
+            // Bots missing from frames in between are not credited for the gap.
+            let bot = self.bots.entry(record.bot_id).or_default();
+            match bot.last {
+                Some((last, x, y)) if last + 1 == self.frames => {
+                    bot.distance += (record.x - x).hypot(record.y - y);
+                }
+                Some(_) => {
+                    bot.times.push(f64::NAN);
+                    bot.distances.push(f64::NAN);
+                }
+                None => {}
+            }
+            bot.last = Some((self.frames, record.x, record.y));
+            bot.times.push(time);
+            bot.distances.push(bot.distance);
+        }
+
+        if seen {
+            self.times.push(time);
+            self.totals
+                .push(self.bots.values().map(|bot| bot.distance).sum());
+        }
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+        for (bot_id, bot) in &self.bots {
+            plot.line(Series {
+                name: format!("Bot {}", bot_id),
+                x: bot.times.clone(),
+                y: bot.distances.iter().map(|d| d * CM_PER_PX).collect(),
+            });
+        }
+
+        let mut swarm = Plot::new(
+            "Distance Travelled by the Swarm Over Time",
+            (&self.time.label(), "Distance (cm)"),
+        );
+        swarm.x.clock = self.time.clock();
+        swarm.y.min = Some(0.0);
+        swarm.legend = false;
+        swarm.line(Series {
+            name: "Swarm".to_string(),
+            x: self.times.clone(),
+            y: self.totals.iter().map(|d| d * CM_PER_PX).collect(),
+        });
+
+        vec![
+            Output::table(self.name(), self.summary()),
+            Output::plot(self.name(), plot),
+            Output::plot(&format!("{}-swarm", self.name()), swarm),
+        ]
+    }
+}
+
+#[derive(Default)]
+pub struct Coverage {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    map: Option<Map>,
+    size: f64,
+    visited: HashSet<Cell>,
+    /// Cells in the order they were first visited in, with the tick of the visit.
+    visits: Vec<(u64, Cell)>,
+    /// Tick of every frame with selected bots, and the visits up to it.
+    frames: Vec<(u64, usize)>,
+}
+
+impl Coverage {
+    pub fn new(options: &Options) -> Self {
+        Coverage {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            map: options.map.clone(),
+            size: options.args::<OccupancyArgs>().grid_size,
+            ..Default::default()
+        }
+    }
+
+    /// Free area in pixels of every cell of the grid.
+    fn free_area(&self) -> BTreeMap<Cell, f64> {
+        let Some(((q0, q1), (r0, r1))) = grid_bounds(&self.visited, self.size, self.map.as_ref())
+        else {
+            return BTreeMap::new();
+        };
+        let walls: Vec<MapObject> = self
+            .map
+            .iter()
+            .flat_map(|map| map.obstacles.iter().cloned().chain(map.borders()))
+            .collect();
+        let step = self.size / SAMPLES as f64;
+
+        let mut cells = BTreeMap::new();
+        for q in q0..=q1 {
+            for r in r0..=r1 {
+                let Some(map) = &self.map else {
+                    cells.insert((q, r), self.size * self.size);
+                    continue;
+                };
+
+                let mut free = 0;
+                for i in 0..SAMPLES {
+                    for j in 0..SAMPLES {
+                        let x = (q as f64 * self.size + (i as f64 + 0.5) * step).min(map.width);
+                        let y = (r as f64 * self.size + (j as f64 + 0.5) * step).min(map.height);
+                        if !walls.iter().any(|wall| wall.contains(x, y)) {
+                            free += 1;
+                        }
+                    }
+                }
+                cells.insert((q, r), free as f64 * step * step);
+            }
+        }
+
+        cells
+    }
+}
+
+impl Metric for Coverage {
+    fn name(&self) -> &'static str {
+        "coverage"
+    }
+
+    fn title(&self) -> String {
+        "Coverage of the Free Map Area Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (
+            self.time.label(),
+            "Fraction of Free Area Visited".to_string(),
+        )
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let mut seen = false;
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+            seen = true;
+
+            let cell = cell(record.x, record.y, self.size);
+            if self.visited.insert(cell) {
+                self.visits.push((frame.tick, cell));
+            }
+        }
+
+        if seen {
+            self.frames.push((frame.tick, self.visits.len()));
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let free = self.free_area();
+        let total: f64 = free.values().sum();
+
+        // Free area visited after each visit, in pixels.
+        let mut area = vec![0.0];
+        for (_, cell) in &self.visits {
+            area.push(area[area.len() - 1] + free.get(cell).copied().unwrap_or(0.0));
+        }
+        let fraction = |visits: usize| {
+            if total > 0.0 {
+                area[visits] / total
+            } else {
+                0.0
+            }
+        };
+
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+        plot.y.max = Some(1.0);
+        plot.legend = false;
+        plot.line(Series {
+            name: "Coverage".to_string(),
+            x: self
+                .frames
+                .iter()
+                .map(|(tick, _)| self.time.value(*tick as f64))
+                .collect(),
+            y: self
+                .frames
+                .iter()
+                .map(|(_, visits)| fraction(*visits))
+                .collect(),
+        });
+
+        // New cells per minute, counted in the minute of the log they were first visited in.
+        let minute = self.time.ticks(60.0);
+        let mut minutes: BTreeMap<u64, usize> = BTreeMap::new();
+        if let (Some((first, _)), Some((last, _))) = (self.frames.first(), self.frames.last()) {
+            for index in (*first as f64 / minute) as u64..=(*last as f64 / minute) as u64 {
+                minutes.insert(index, 0);
+            }
+        }
+        for (tick, _) in &self.visits {
+            *minutes.entry((*tick as f64 / minute) as u64).or_default() += 1;
+        }
+
+        let mut rate = Plot::new(
+            "Exploration Rate",
+            (&self.time.label(), "New Cells per Minute"),
+        );
+        rate.x.clock = self.time.clock();
+        rate.y.min = Some(0.0);
+        rate.legend = false;
+        rate.layers.push(Layer::Line {
+            series: Series {
+                name: "New Cells".to_string(),
+                x: minutes
+                    .keys()
+                    .map(|index| self.time.value((*index as f64 + 0.5) * minute))
+                    .collect(),
+                y: minutes.values().map(|count| *count as f64).collect(),
+            },
+            color: Some(Color::BLUE),
+            width: 2.0,
+        });
+
+        let reached = |target: f64| {
+            self.frames
+                .iter()
+                .find(|(_, visits)| fraction(*visits) >= target)
+                .map_or("-".to_string(), |(tick, _)| self.time.format(*tick as f64))
+        };
+        let unit = self.time.unit.name();
+        let half = format!("Time to 50% ({})", unit);
+        let most = format!("Time to 90% ({})", unit);
+        let mut table = Table::new(
+            "Exploration Coverage",
+            &[
+                "Cell Size (px)",
+                "Free Area (px²)",
+                "Visited Area (px²)",
+                "Visited Cells",
+                "Coverage (%)",
+                &half,
+                &most,
+            ],
+        );
+        table.push(vec![
+            self.size.to_string(),
+            format!("{:.0}", total),
+            format!("{:.0}", area[area.len() - 1]),
+            self.visits.len().to_string(),
+            format!("{:.1}", fraction(self.visits.len()) * 100.0),
+            reached(0.5),
+            reached(0.9),
+        ]);
+
+        vec![
+            Output::table(self.name(), table),
+            Output::plot(self.name(), plot),
+            Output::plot(&format!("{}-rate", self.name()), rate),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{BotStatus, ObjectType, Record};
+
+    fn map(width: f64, height: f64, obstacles: &[(f64, f64, f64, f64)]) -> Map {
+        let object = |(x, y, width, height): (f64, f64, f64, f64)| {
+            format!(
+                r#"{{"x": {}, "y": {}, "width": {}, "height": {}, "mass": 500}}"#,
+                x, y, width, height
+            )
+        };
+        let obstacles: Vec<String> = obstacles.iter().copied().map(object).collect();
+        let station = object((0.0, 0.0, 20.0, 20.0));
+        serde_json::from_str(&format!(
+            r#"{{"width": {}, "height": {}, "bots": [], "obstacles": [{}],
+                "station": {}, "target_station": {}}}"#,
+            width,
+            height,
+            obstacles.join(","),
+            station,
+            station
+        ))
+        .expect("Invalid map")
+    }
+
+    fn coverage(map: Option<Map>, size: f64, positions: &[(f64, f64)]) -> Coverage {
+        let mut coverage = Coverage {
+            map,
+            size,
+            ..Default::default()
+        };
+        for (i, (x, y)) in positions.iter().enumerate() {
+            coverage.accumulate(&Frame {
+                tick: i as u64 * 2,
+                bots: vec![Record {
+                    tick: i as u64 * 2,
+                    bot_id: 0,
+                    energy: 100.0,
+                    data: Vec::new(),
+                    x: *x,
+                    y: *y,
+                    vel_x: 0.0,
+                    vel_y: 0.0,
+                    rotation: 0.0,
+                    status: BotStatus::Active,
+                    color: [0.0; 4],
+                    r#type: ObjectType::Bot,
+                }],
+                station: None,
+                target_station: None,
+            });
+        }
+        coverage
+    }
+
+    #[test]
+    fn counts_whole_cells_without_a_map() {
+        let free = coverage(None, 10.0, &[(5.0, 5.0), (25.0, 15.0)]).free_area();
+
+        assert_eq!(free.len(), 6);
+        assert!(free.values().all(|area| *area == 100.0));
+    }
+
+    #[test]
+    fn leaves_out_the_border_walls() {
+        let free = coverage(Some(map(100.0, 100.0, &[])), 50.0, &[]).free_area();
+
+        // The 10 px walls take a strip off two sides of every cell.
+        assert_eq!(
+            free,
+            BTreeMap::from([
+                ((0, 0), 1600.0),
+                ((0, 1), 1600.0),
+                ((1, 0), 1600.0),
+                ((1, 1), 1600.0),
+            ])
+        );
+    }
+
+    #[test]
+    fn leaves_out_obstacles_and_the_map_beyond_its_edge() {
+        let free = coverage(
+            Some(map(120.0, 100.0, &[(50.0, 50.0, 60.0, 40.0)])),
+            50.0,
+            &[],
+        )
+        .free_area();
+
+        assert_eq!(free[&(0, 0)], 1600.0);
+        assert_eq!(free[&(1, 1)], 0.0);
+        // The last column is only 20 px wide, half of it wall.
+        assert_eq!(free[&(2, 0)], 400.0);
+        assert_eq!(free.len(), 6);
+    }
+
+    #[test]
+    fn weighs_visits_by_free_area() {
+        let map = map(100.0, 100.0, &[(50.0, 50.0, 40.0, 40.0)]);
+        let coverage = coverage(Some(map), 50.0, &[(20.0, 20.0), (70.0, 70.0), (20.0, 30.0)]);
+
+        let outputs = Box::new(coverage).finish();
+        let Some(Output::Table { table, .. }) = outputs.first() else {
+            panic!("No table");
+        };
+        assert_eq!(
+            table.rows[0][1..5],
+            ["4800", "1600", "2", "33.3"].map(String::from)
+        );
+    }
+}
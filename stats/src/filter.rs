@@ -0,0 +1,268 @@
+//! Tick windows and map regions that restrict which parts of a replay reach the metrics.
+//!
+//! Filtering happens on the frames before they are handed to the [`Engine`](crate::engine::Engine),
+//! so every metric sees the same episode of the run.
+
+use std::str::FromStr;
+
+use crate::replay::Frame;
+use crate::time::TimeBase;
+
+/// An area of the map in log coordinates (pixels, y pointing down).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    Rect { x: (f64, f64), y: (f64, f64) },
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Region {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        match self {
+            Region::Rect { x: xs, y: ys } => xs.0 <= x && x <= xs.1 && ys.0 <= y && y <= ys.1,
+            Region::Polygon(points) => {
+                // Even-odd rule: count the edges crossed by a ray going right from the point. Points
+                // on an edge are inside, like on the edge of a rectangle.
+                let mut inside = false;
+                for (i, (x1, y1)) in points.iter().enumerate() {
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    if on_segment((x, y), (*x1, *y1), (x2, y2)) {
+                        return true;
+                    }
+                    if (*y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+}
+
+/// Whether `(x, y)` lies on the segment from `a` to `b`, up to rounding.
+fn on_segment((x, y): (f64, f64), a: (f64, f64), b: (f64, f64)) -> bool {
+    let cross = (b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0);
+    cross.abs() <= 1e-9 * (b.0 - a.0).hypot(b.1 - a.1)
+        && a.0.min(b.0) <= x
+        && x <= a.0.max(b.0)
+        && a.1.min(b.1) <= y
+        && y <= a.1.max(b.1)
+}
+
+fn parse_numbers(s: &str) -> Result<Vec<f64>, String> {
+    s.split([',', ' ', ';'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.parse()
+                .map_err(|_| format!("invalid coordinate: {}", part))
+        })
+        .collect()
+}
+
+/// Parses `X0,Y0,X1,Y1` into a rectangle.
+fn parse_rect(s: &str) -> Result<Region, String> {
+    match parse_numbers(s)?[..] {
+        [x0, y0, x1, y1] => Ok(Region::Rect {
+            x: (x0.min(x1), x0.max(x1)),
+            y: (y0.min(y1), y0.max(y1)),
+        }),
+        _ => Err("a rectangle needs four numbers: X0,Y0,X1,Y1".to_string()),
+    }
+}
+
+/// Parses `X,Y X,Y X,Y ...` into a polygon.
+fn parse_polygon(s: &str) -> Result<Region, String> {
+    let numbers = parse_numbers(s)?;
+    if numbers.len() < 6 || numbers.len() % 2 != 0 {
+        return Err("a polygon needs at least three X,Y points".to_string());
+    }
+
+    Ok(Region::Polygon(
+        numbers
+            .chunks(2)
+            .map(|point| (point[0], point[1]))
+            .collect(),
+    ))
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parses `rect:X0,Y0,X1,Y1` or `polygon:X,Y X,Y X,Y ...`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("rect", rect)) => parse_rect(rect),
+            Some(("polygon", polygon)) => parse_polygon(polygon),
+            _ => Err("expected rect:X0,Y0,X1,Y1 or polygon:X,Y X,Y X,Y ...".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub from_tick: Option<u64>,
+    pub to_tick: Option<u64>,
+    pub region: Option<Region>,
+}
+
+impl Filter {
+    /// Whether no frame after `tick` can pass the filter.
+    pub fn done(&self, tick: u64) -> bool {
+        self.to_tick.is_some_and(|to_tick| tick > to_tick)
+    }
+
+    /// Drops frames outside the tick window and bots outside the region.
+    pub fn apply(&self, mut frame: Frame) -> Option<Frame> {
+        if self
+            .from_tick
+            .is_some_and(|from_tick| frame.tick < from_tick)
+            || self.done(frame.tick)
+        {
+            return None;
+        }
+
+        if let Some(region) = &self.region {
+            frame
+                .bots
+                .retain(|record| region.contains(record.x, record.y));
+        }
+
+        Some(frame)
+    }
+
+    /// Filters a stream of frames, which stops reading once the end of the tick window is passed.
+    pub fn frames<'a, I>(
+        &'a self,
+        frames: I,
+    ) -> impl Iterator<Item = Result<Frame, csv::Error>> + 'a
+    where
+        I: IntoIterator<Item = Result<Frame, csv::Error>>,
+        I::IntoIter: 'a,
+    {
+        frames
+            .into_iter()
+            .take_while(|frame| !frame.as_ref().is_ok_and(|frame| self.done(frame.tick)))
+            .filter_map(|frame| match frame {
+                Ok(frame) => self.apply(frame).map(Ok),
+                Err(err) => Some(Err(err)),
+            })
+    }
+}
+
+// Not a doc comment, see `PlotArgs`.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct FilterArgs {
+    /// Ignore frames before this tick
+    #[arg(long, conflicts_with = "from")]
+    pub from_tick: Option<u64>,
+    /// Ignore frames after this tick
+    #[arg(long, conflicts_with = "to")]
+    pub to_tick: Option<u64>,
+    /// Ignore frames before this time, in the time unit
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Ignore frames after this time, in the time unit
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Only keep bots inside rect:X0,Y0,X1,Y1 or polygon:"X,Y X,Y X,Y ..." (log coordinates)
+    #[arg(long)]
+    pub region: Option<Region>,
+}
+
+impl FilterArgs {
+    pub fn filter(&self, time: &TimeBase) -> Result<Filter, String> {
+        let tick = |value: &Option<String>| -> Result<Option<u64>, String> {
+            value
+                .as_deref()
+                .map(|value| time.parse(value).map(|ticks| ticks.round().max(0.0) as u64))
+                .transpose()
+        };
+
+        Ok(Filter {
+            from_tick: self.from_tick.or(tick(&self.from)?),
+            to_tick: self.to_tick.or(tick(&self.to)?),
+            region: self.region.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(s: &str) -> Region {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn normalises_rect_corners() {
+        let expected = Region::Rect {
+            x: (10.0, 50.0),
+            y: (20.0, 40.0),
+        };
+        assert_eq!(region("rect:10,20,50,40"), expected);
+        assert_eq!(region("rect:50,40,10,20"), expected);
+        assert_eq!(region("rect:50,20,10,40"), expected);
+        assert_eq!(region("rect:10 40; 50 20"), expected);
+    }
+
+    #[test]
+    fn rect_contains_its_edges() {
+        let rect = region("rect:50,40,10,20");
+        assert!(rect.contains(30.0, 30.0));
+        assert!(rect.contains(10.0, 20.0));
+        assert!(rect.contains(50.0, 30.0));
+        assert!(!rect.contains(50.1, 30.0));
+        assert!(!rect.contains(30.0, 19.9));
+    }
+
+    #[test]
+    fn rejects_malformed_regions() {
+        assert!("rect:1,2,3".parse::<Region>().is_err());
+        assert!("rect:1,2,3,x".parse::<Region>().is_err());
+        assert!("polygon:0,0 1,1".parse::<Region>().is_err());
+        assert!("polygon:0,0 1,1 2".parse::<Region>().is_err());
+        assert!("circle:0,0,5".parse::<Region>().is_err());
+        assert!("0,0,1,1".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn parses_polygon_points() {
+        assert_eq!(
+            region("polygon:0,0 10,0 5,8.5"),
+            Region::Polygon(vec![(0.0, 0.0), (10.0, 0.0), (5.0, 8.5)])
+        );
+    }
+
+    #[test]
+    fn polygon_contains_its_edges_and_corners() {
+        let square = region("polygon:0,0 10,0 10,10 0,10");
+        for (x, y) in [
+            (0.0, 5.0),
+            (10.0, 5.0),
+            (5.0, 0.0),
+            (5.0, 10.0),
+            (10.0, 10.0),
+        ] {
+            assert!(square.contains(x, y), "({}, {})", x, y);
+        }
+        assert!(!square.contains(10.5, 5.0));
+
+        let triangle = region("polygon:0,0 30,0 0,30");
+        assert!(triangle.contains(10.0, 20.0));
+        assert!(triangle.contains(0.1 * 30.0, 0.9 * 30.0));
+        assert!(!triangle.contains(15.1, 15.1));
+    }
+
+    #[test]
+    fn concave_polygon_excludes_its_notch() {
+        // A U shape, open at the top between x = 10 and x = 20.
+        let u = region("polygon:0,0 10,0 10,20 20,20 20,0 30,0 30,30 0,30");
+        assert!(u.contains(5.0, 5.0));
+        assert!(u.contains(25.0, 5.0));
+        assert!(u.contains(15.0, 25.0));
+        assert!(!u.contains(15.0, 10.0));
+        assert!(!u.contains(15.0, 0.0));
+        assert!(u.contains(15.0, 20.0));
+        assert!(!u.contains(-1.0, 10.0));
+    }
+}
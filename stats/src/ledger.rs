@@ -0,0 +1,497 @@
+//! Accounts for every change in a bot's energy with the simulator's power model.
+//!
+//! Between two samples a bot drains [`power_usage`] once for every second that starts, at the speed
+//! it was logged with. The simulator drains accelerating bots as if at [`MAX_SPEED`]; the log has
+//! no target velocity, so a bot counts as accelerating when its velocity changed since the last
+//! sample. A change that ends right before a second starts is drained at the lower speed, which
+//! leaves at most `0.542 * MAX_SPEED` (0.03 J) unexplained. What the drain does not explain is charging when the bot ends up full,
+//! trophallaxis when it was in contact with another bot, or being killed when it drops to zero.
+//! Anything else beyond the tolerance is a residual, and flagged.
+
+use std::collections::BTreeMap;
+
+use crate::aggregate::{plot, selected};
+use crate::constants::{power_usage, MAX_ENERGY, MAX_SPEED};
+use crate::encounter::in_contact;
+use crate::metric::{Metric, Options, Output};
+use crate::plot::Series;
+use crate::replay::{BotStatus, Frame, Record};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+// Options of the energy ledger (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq)]
+pub struct LedgerArgs {
+    /// Largest energy change in joules between two samples the power model may leave unexplained
+    #[arg(long, default_value_t = 0.1)]
+    pub energy_tolerance: f64,
+}
+
+impl Default for LedgerArgs {
+    fn default() -> Self {
+        LedgerArgs {
+            energy_tolerance: 0.1,
+        }
+    }
+}
+
+/// Flagged changes printed to the terminal.
+const RESIDUALS_PRINTED: usize = 20;
+
+/// Energy in joules per cause, losses are negative.
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    movement: f64,
+    gained: f64,
+    lost: f64,
+    charged: f64,
+    killed: f64,
+    residual: f64,
+}
+
+impl Totals {
+    const CAUSES: [&'static str; 6] = [
+        "Movement",
+        "Trophallaxis Gained",
+        "Trophallaxis Lost",
+        "Charging",
+        "Killed",
+        "Residual",
+    ];
+
+    /// Values in the order of [`Totals::CAUSES`].
+    fn values(&self) -> [f64; 6] {
+        [
+            self.movement,
+            self.gained,
+            self.lost,
+            self.charged,
+            self.killed,
+            self.residual,
+        ]
+    }
+
+    fn add(&mut self, other: &Totals) {
+        self.movement += other.movement;
+        self.gained += other.gained;
+        self.lost += other.lost;
+        self.charged += other.charged;
+        self.killed += other.killed;
+        self.residual += other.residual;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Sample {
+    frame: usize,
+    tick: u64,
+    energy: f64,
+    velocity: (f64, f64),
+    status: BotStatus,
+}
+
+#[derive(Debug, Clone)]
+struct Account {
+    first: f64,
+    last: Sample,
+    totals: Totals,
+    flagged: usize,
+}
+
+/// A change the power model and the statuses do not explain.
+#[derive(Debug, Clone)]
+struct Flag {
+    tick: u64,
+    bot_id: u16,
+    status: BotStatus,
+    before: f64,
+    after: f64,
+    expected: f64,
+}
+
+#[derive(Default)]
+pub struct EnergyLedger {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    tolerance: f64,
+    frames: usize,
+    bots: BTreeMap<u16, Account>,
+    flags: Vec<Flag>,
+    /// Running swarm totals per frame.
+    swarm: Vec<(f64, Totals)>,
+}
+
+impl EnergyLedger {
+    pub fn new(options: &Options) -> Self {
+        EnergyLedger {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            tolerance: options.args::<LedgerArgs>().energy_tolerance,
+            ..Default::default()
+        }
+    }
+
+    /// Splits the change since the bot's last sample over the causes.
+    fn book(&self, last: &Sample, tick: u64, record: &Record) -> (Totals, Option<Flag>) {
+        let tps = self.time.ticks_per_second;
+        let seconds = ((tick as f64 / tps).floor() - (last.tick as f64 / tps).floor()).max(0.0);
+        let speed = if (record.vel_x, record.vel_y) == last.velocity {
+            record.vel_x.hypot(record.vel_y)
+        } else {
+            MAX_SPEED
+        };
+        // Energy never drops below zero.
+        let drain = (seconds * power_usage(speed)).min(last.energy);
+        let unexplained = record.energy - last.energy + drain;
+
+        let mut totals = Totals {
+            movement: -drain,
+            ..Default::default()
+        };
+        let mut flag = None;
+
+        if unexplained.abs() <= self.tolerance {
+            totals.residual = unexplained;
+        } else if record.status == BotStatus::Depleted && record.energy == 0.0 {
+            totals.killed = unexplained;
+        } else if unexplained > 0.0 && MAX_ENERGY - record.energy <= drain + self.tolerance {
+            totals.charged = unexplained;
+        } else if in_contact(&last.status) || in_contact(&record.status) {
+            if unexplained > 0.0 {
+                totals.gained = unexplained;
+            } else {
+                totals.lost = unexplained;
+            }
+        } else {
+            totals.residual = unexplained;
+            flag = Some(Flag {
+                tick,
+                bot_id: record.bot_id,
+                status: record.status.clone(),
+                before: last.energy,
+                after: record.energy,
+                expected: -drain,
+            });
+        }
+
+        (totals, flag)
+    }
+
+    fn summary(&self) -> Table {
+        let mut table = Table::new(
+            "Energy Ledger (J)",
+            &[
+                &["Bot", "Start", "End", "Change"],
+                &Totals::CAUSES[..],
+                &["Flagged"],
+            ]
+            .concat(),
+        );
+
+        for (bot_id, account) in &self.bots {
+            let energy = |value: f64| format!("{:.2}", value);
+
+            let mut row = vec![
+                bot_id.to_string(),
+                energy(account.first),
+                energy(account.last.energy),
+                energy(account.last.energy - account.first),
+            ];
+            row.extend(account.totals.values().map(energy));
+            row.push(account.flagged.to_string());
+            table.push(row);
+        }
+
+        table
+    }
+
+    /// Flagged changes, largest residual first.
+    fn residuals(&self) -> Table {
+        let time = format!("Time ({})", self.time.unit.name());
+        let mut table = Table::new(
+            "Unexplained Energy Changes (J), Largest First",
+            &[
+                &time,
+                "Bot",
+                "Status",
+                "Before",
+                "After",
+                "Expected Change",
+                "Residual",
+            ],
+        );
+
+        let residual = |flag: &Flag| flag.after - flag.before - flag.expected;
+        let mut flags: Vec<&Flag> = self.flags.iter().collect();
+        flags.sort_by(|a, b| residual(b).abs().total_cmp(&residual(a).abs()));
+
+        table.limit(RESIDUALS_PRINTED);
+        for flag in flags {
+            table.push(vec![
+                self.time.format(flag.tick as f64),
+                flag.bot_id.to_string(),
+                flag.status.to_string(),
+                format!("{:.3}", flag.before),
+                format!("{:.3}", flag.after),
+                format!("{:.3}", flag.expected),
+                format!("{:.3}", residual(flag)),
+            ]);
+        }
+
+        table
+    }
+}
+
+impl Metric for EnergyLedger {
+    fn name(&self) -> &'static str {
+        "energy-ledger"
+    }
+
+    fn title(&self) -> String {
+        "Swarm Energy Ledger Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Cumulative Energy (J)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let mut booked = Totals::default();
+        let mut any = false;
+
+        for record in frame.bots.iter() {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+            any = true;
+
+            let sample = Sample {
+                frame: self.frames,
+                tick: frame.tick,
+                energy: record.energy,
+                velocity: (record.vel_x, record.vel_y),
+                status: record.status.clone(),
+            };
+
+            let Some(account) = self.bots.get(&record.bot_id) else {
+                self.bots.insert(
+                    record.bot_id,
+                    Account {
+                        first: record.energy,
+                        last: sample,
+                        totals: Totals::default(),
+                        flagged: 0,
+                    },
+                );
+                continue;
+            };
+
+            // Changes over frames the bot is missing from can not be split up.
+            let (totals, flag) = if account.last.frame + 1 == self.frames {
+                self.book(&account.last, frame.tick, record)
+            } else {
+                (Totals::default(), None)
+            };
+
+            let account = self.bots.get_mut(&record.bot_id).expect("Unknown bot");
+            account.last = sample;
+            account.totals.add(&totals);
+            booked.add(&totals);
+            if let Some(flag) = flag {
+                account.flagged += 1;
+                self.flags.push(flag);
+            }
+        }
+
+        if any {
+            let mut running = self.swarm.last().map(|(_, t)| *t).unwrap_or_default();
+            running.add(&booked);
+            self.swarm
+                .push((self.time.value(frame.tick as f64), running));
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+
+        for (i, name) in Totals::CAUSES.iter().enumerate() {
+            let (x, y) = self.swarm.iter().map(|(x, t)| (*x, t.values()[i])).unzip();
+            plot.line(Series {
+                name: name.to_string(),
+                x,
+                y,
+            });
+        }
+
+        vec![
+            Output::table(self.name(), self.summary()),
+            Output::table(&format!("{}-residuals", self.name()), self.residuals()),
+            Output::plot(self.name(), plot),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ObjectType;
+
+    const VELOCITY: (f64, f64) = (0.03, 0.04);
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn ledger() -> EnergyLedger {
+        EnergyLedger {
+            tolerance: 0.1,
+            ..Default::default()
+        }
+    }
+
+    fn sample(energy: f64, status: BotStatus) -> Sample {
+        Sample {
+            frame: 0,
+            tick: 58,
+            energy,
+            velocity: VELOCITY,
+            status,
+        }
+    }
+
+    fn record(tick: u64, energy: f64, (vel_x, vel_y): (f64, f64), status: BotStatus) -> Record {
+        Record {
+            tick,
+            bot_id: 3,
+            energy,
+            data: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            vel_x,
+            vel_y,
+            rotation: 0.0,
+            status,
+            color: [0.0; 4],
+            r#type: ObjectType::Bot,
+        }
+    }
+
+    /// Books a change from `before` at tick 58 to `after` at tick 60, where a second starts.
+    fn book(
+        before: (f64, BotStatus),
+        after: (f64, (f64, f64), BotStatus),
+    ) -> (Totals, Option<Flag>) {
+        let record = record(60, after.0, after.1, after.2);
+        ledger().book(&sample(before.0, before.1), 60, &record)
+    }
+
+    #[test]
+    fn drains_once_per_second_at_the_logged_speed() {
+        let drain = power_usage(0.05);
+        let (totals, flag) = book(
+            (500.0, BotStatus::Active),
+            (500.0 - drain + 0.05, VELOCITY, BotStatus::Active),
+        );
+        assert_close(totals.movement, -drain);
+        assert_close(totals.residual, 0.05);
+        assert!(flag.is_none());
+
+        // No second starts between ticks 60 and 62.
+        let record = record(62, 500.0, VELOCITY, BotStatus::Active);
+        let last = Sample {
+            tick: 60,
+            ..sample(500.0, BotStatus::Active)
+        };
+        let (totals, flag) = ledger().book(&last, 62, &record);
+        assert_eq!(totals.values(), [0.0; 6]);
+        assert!(flag.is_none());
+    }
+
+    #[test]
+    fn drains_accelerating_bots_at_full_speed() {
+        let drain = power_usage(MAX_SPEED);
+        let (totals, flag) = book(
+            (500.0, BotStatus::Active),
+            (500.0 - drain, (0.031, 0.04), BotStatus::Active),
+        );
+        assert_close(totals.movement, -drain);
+        assert_close(totals.residual, 0.0);
+        assert!(flag.is_none());
+    }
+
+    #[test]
+    fn books_the_rest_of_a_killed_bot() {
+        let (totals, flag) = book(
+            (50.0, BotStatus::Active),
+            (0.0, VELOCITY, BotStatus::Depleted),
+        );
+        assert_close(totals.movement, -power_usage(0.05));
+        assert_close(totals.killed, -50.0 + power_usage(0.05));
+        assert!(flag.is_none());
+
+        // The drain stops at zero.
+        let (totals, _) = book(
+            (0.1, BotStatus::Active),
+            (0.0, VELOCITY, BotStatus::Depleted),
+        );
+        assert_close(totals.movement, -0.1);
+        assert_close(totals.killed, 0.0);
+    }
+
+    #[test]
+    fn books_charging_up_to_full() {
+        let drain = power_usage(0.05);
+        let (totals, flag) = book(
+            (900.0, BotStatus::Active),
+            (MAX_ENERGY - drain, VELOCITY, BotStatus::Active),
+        );
+        assert_close(totals.charged, 180.0);
+        assert_close(totals.movement, -drain);
+        assert!(flag.is_none());
+    }
+
+    #[test]
+    fn books_trophallaxis_by_direction() {
+        let drain = power_usage(0.05);
+        let (totals, flag) = book(
+            (500.0, BotStatus::Active),
+            (520.0 - drain, VELOCITY, BotStatus::Trophallaxis),
+        );
+        assert_close(totals.gained, 20.0);
+        assert_eq!(totals.lost, 0.0);
+        assert!(flag.is_none());
+
+        // Contact that ended by the next sample still counts.
+        let (totals, flag) = book(
+            (500.0, BotStatus::Trophallaxis),
+            (480.0 - drain, VELOCITY, BotStatus::Active),
+        );
+        assert_close(totals.lost, -20.0);
+        assert_eq!(totals.gained, 0.0);
+        assert!(flag.is_none());
+    }
+
+    #[test]
+    fn flags_what_nothing_explains() {
+        let drain = power_usage(0.05);
+        let (totals, flag) = book(
+            (500.0, BotStatus::Active),
+            (495.0 - drain, VELOCITY, BotStatus::Active),
+        );
+        assert_close(totals.residual, -5.0);
+
+        let flag = flag.expect("Not flagged");
+        assert_eq!((flag.tick, flag.bot_id), (60, 3));
+        assert_eq!(flag.before, 500.0);
+        assert_close(flag.expected, -drain);
+        assert_close(flag.after - flag.before - flag.expected, -5.0);
+    }
+}
@@ -0,0 +1,26 @@
+pub mod aggregate;
+pub mod cohesion;
+pub mod constants;
+pub mod encounter;
+pub mod engine;
+pub mod experiment;
+pub mod exploration;
+pub mod filter;
+pub mod ledger;
+pub mod map;
+pub mod metric;
+pub mod movement;
+pub mod network;
+pub mod occupancy;
+pub mod plot;
+pub mod propagation;
+pub mod provenance;
+pub mod replay;
+pub mod speed;
+pub mod stuck;
+pub mod survival;
+pub mod table;
+pub mod time;
+pub mod transition;
+
+mod de;
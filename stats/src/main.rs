@@ -1,294 +1,98 @@
-use std::sync::Arc;
-use std::thread;
-
-use clap::Parser;
-use gnuplot::{AxesCommon, Caption, Figure, PlotOption::LineWidth};
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use capbot_stats::engine::Engine;
+use capbot_stats::filter::FilterArgs;
+use capbot_stats::map::Map;
+use capbot_stats::metric::{Options, Output, Registry};
+use capbot_stats::plot::PlotArgs;
+use capbot_stats::replay::FrameReader;
+use capbot_stats::time::{TimeBase, TimeUnit};
+use clap::builder::PossibleValuesParser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use std::fs;
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Args {
     #[arg(long, required = true)]
     log_file: String,
-    #[arg(long, value_delimiter = ' ', num_args = 1.., default_values_t = Stat::all())]
-    stats: Vec<Stat>,
+    #[arg(long, value_delimiter = ' ', num_args = 1..)]
+    stats: Vec<String>,
     #[arg(long, value_delimiter = ' ', num_args = 1..)]
     bots: Vec<u16>,
+    /// Map file the log was recorded on, e.g. configurations/maps/maze.json
     #[arg(long)]
+    map: Option<String>,
+    /// Shorthand for `--time-unit seconds`
+    #[arg(long, conflicts_with = "unit")]
     seconds: bool,
+    #[command(flatten)]
+    time: TimeBase,
+    #[command(flatten)]
+    filter: FilterArgs,
+    #[command(flatten)]
+    plot: PlotArgs,
 }
 
-#[derive(clap::ValueEnum, Clone, Debug, Serialize)]
-#[serde(rename_all = "kebab-case")]
-enum Stat {
-    DataPerBot,
-    EnergyPerBot,
-    StatusPerBot,
-    DataCumulative,
-    EnergyCumulative,
-    Locations,
-}
-
-impl std::fmt::Display for Stat {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::DataPerBot => write!(f, "data-per-bot"),
-            Self::EnergyPerBot => write!(f, "energy-per-bot"),
-            Self::StatusPerBot => write!(f, "status-per-bot"),
-            Self::DataCumulative => write!(f, "data-cumulative"),
-            Self::EnergyCumulative => write!(f, "energy-cumulative"),
-            Self::Locations => write!(f, "locations"),
-        }
-    }
-}
+/// The arguments of `capbot-stats`, and all matches for the options of the metrics.
+fn parse_args(registry: &Registry) -> (Args, ArgMatches) {
+    let command = registry.augment(Args::command()).mut_arg("stats", |arg| {
+        arg.value_parser(PossibleValuesParser::new(registry.names()))
+            .default_values(registry.defaults())
+    });
 
-#[derive(Debug, Deserialize)]
-struct Record {
-    tick: u64,
-    bot_id: u16,
-    energy: f64,
-    #[serde(deserialize_with = "parse_data_field")]
-    data: Vec<u8>,
-    x: f64,
-    y: f64,
-    //    vel_x: f32,
-    //    vel_y: f32,
-    status: String,
-    r#type: String,
+    let matches = command.get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    (args, matches)
 }
 
-fn parse_data_field<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let s: String = Deserialize::deserialize(deserializer)?;
-
-    if s.trim().is_empty() {
-        return Ok(Vec::new());
-    }
-
-    s.split(',')
-        .map(|s| s.trim().parse::<u8>().map_err(serde::de::Error::custom))
-        .collect()
-}
-
-impl Stat {
-    fn all() -> Vec<Stat> {
-        vec![
-            Stat::DataPerBot,
-            Stat::EnergyPerBot,
-            Stat::StatusPerBot,
-            Stat::DataCumulative,
-            Stat::EnergyCumulative,
-            Stat::Locations,
-        ]
-    }
-
-    pub fn title(&self) -> &'static str {
-        match self {
-            Self::DataPerBot => "Bot Data Over Time",
-            Self::EnergyPerBot => "Bot Energy Over Time",
-            Self::StatusPerBot => "Bot Status Over Time",
-            Self::DataCumulative => "Total Data In System (Bots) Over Time",
-            Self::EnergyCumulative => "Total Energy In System (Bots) Over Time",
-            Self::Locations => "Locations of Bots Over Time",
-        }
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let registry = Registry::default();
+    let (mut args, matches) = parse_args(&registry);
+    if args.seconds {
+        args.time.unit = TimeUnit::Seconds;
     }
 
-    pub fn labels(&self) -> (&'static str, &'static str) {
-        match self {
-            Self::DataPerBot => ("Time (ticks)", "Presence of Data (0 or 1)"),
-            Self::EnergyPerBot => ("Time (ticks)", "Energy Per Bot (J)"),
-            Self::StatusPerBot => ("Time (ticks)", "Status"),
-            Self::DataCumulative => ("Time (ticks)", "Total Data In System (Bots)"),
-            Self::EnergyCumulative => ("Time (ticks)", "Total Energy In System (J)"),
-            Self::Locations => ("X Coordinate", "Y Coordinate"),
-        }
+    let options = Options {
+        bot_ids: args.bots,
+        time: args.time,
+        map: args.map.as_deref().map(Map::load).transpose()?,
+        matches: Some(matches),
+    };
+    let filter = args.filter.filter(&args.time)?;
+    let renderer = args.plot.renderer()?;
+
+    let mut engine = Engine::new();
+    for name in &args.stats {
+        engine.register(registry.build(name, &options).expect("Unregistered metric"));
     }
 
-    pub fn data(
-        &self,
-        records: &Vec<Record>,
-        bot_ids: &Vec<u16>,
-    ) -> Vec<(String, Vec<f64>, Vec<f64>)> {
-        let bot_records: Vec<&Record> = records.iter().filter(|r| r.r#type == "bot").collect();
-        let mut grouped_by_tick: BTreeMap<u64, Vec<&Record>> = BTreeMap::new();
-        let mut grouped_by_bot: BTreeMap<u16, Vec<&Record>> = BTreeMap::new();
-
-        for record in &bot_records {
-            grouped_by_tick.entry(record.tick).or_default().push(record);
-
-            if !bot_ids.is_empty() && !bot_ids.contains(&record.bot_id) {
-                continue;
-            }
-
-            grouped_by_bot
-                .entry(record.bot_id)
-                .or_default()
-                .push(record);
-        }
-
-        let ticks: Vec<f64> = grouped_by_tick.keys().map(|&i| i as f64).collect();
-
-        match self {
-            Self::DataPerBot => {
-                let data: Vec<u8> = bot_records
-                    .iter()
-                    .map(|r| r.data.clone())
-                    .max_by_key(|v| v.len())
-                    .expect("Couldn't get data vector");
-
-                grouped_by_bot
-                    .iter()
-                    .flat_map(|(bot_id, records)| {
-                        data.iter().map(|value| {
-                            (
-                                format!("Bot {} - Data {}", bot_id.clone(), value),
-                                ticks.clone(),
-                                records
-                                    .iter()
-                                    .map(|r| if r.data.contains(&value) { 1.0 } else { 0.0 })
-                                    .collect(),
-                            )
-                        })
-                    })
-                    .collect()
-            }
-            Self::EnergyPerBot => grouped_by_bot
-                .iter()
-                .map(|(bot_id, records)| {
-                    (
-                        format!("Bot {}", bot_id),
-                        ticks.clone(),
-                        records.iter().map(|r| r.energy as f64).collect(),
-                    )
-                })
-                .collect(),
-            Self::StatusPerBot => grouped_by_bot
-                .iter()
-                .map(|(bot_id, records)| {
-                    (
-                        format!("Bot {}", bot_id),
-                        ticks.clone(),
-                        records
-                            .iter()
-                            .map(|r| match r.status.as_str() {
-                                "abort" => 0.0,
-                                "active_aborting" => 0.0,
-                                "active" => 1.0,
-                                "trophallaxis" => 2.0,
-                                "data_transfer" => 3.0,
-                                _ => unimplemented!("status {} is not implemented", r.status),
-                            })
-                            .collect(),
-                    )
-                })
-                .collect(),
-            Self::DataCumulative => {
-                let data: Vec<u8> = bot_records
-                    .iter()
-                    .map(|r| r.data.clone())
-                    .max_by_key(|v| v.len())
-                    .expect("Couldn't get data vector");
-
-                data.iter()
-                    .map(|value| {
-                        (
-                            value.to_string(),
-                            ticks.clone(),
-                            grouped_by_tick
-                                .values()
-                                .map(|records| {
-                                    records
-                                        .iter()
-                                        .filter(|record| record.data.contains(&value))
-                                        .count() as f64
-                                })
-                                .collect::<Vec<_>>(),
-                        )
-                    })
-                    .collect()
-            }
-
-            Self::EnergyCumulative => vec![(
-                "Total Energy".to_string(),
-                ticks,
-                grouped_by_tick
-                    .values()
-                    .map(|records| records.iter().map(|record| record.energy as f64).sum())
-                    .collect(),
-            )],
-            Self::Locations => {
-                let max_y = grouped_by_bot
-                    .values()
-                    .flat_map(|records| records.iter().map(|r| r.y))
-                    .fold(f64::NEG_INFINITY, f64::max);
-
-                grouped_by_bot
-                    .iter()
-                    .map(|(bot_id, records)| {
-                        (
-                            format!("Bot {}", bot_id),
-                            records.iter().map(|r| r.x).collect(),
-                            records.iter().map(|r| max_y - r.y).collect(),
-                        )
-                    })
-                    .collect()
+    let outputs = engine
+        .run(filter.frames(FrameReader::open(&args.log_file)?))
+        .expect("Error while reading in records");
+
+    for output in outputs.into_iter().flatten() {
+        match output {
+            Output::Plot { name, plot } => renderer.render(&name, &plot)?,
+            Output::Table { name, table } => {
+                table.print();
+                if let Some(output_dir) = &args.plot.output_dir {
+                    let path = output_dir.join(format!("{}.csv", name));
+                    table.write_csv(&path)?;
+                    println!("Saved {}", path.display());
+                }
             }
-        }
-    }
-}
-
-fn parse_csv(path: String) -> Result<Vec<Record>, Box<dyn std::error::Error>> {
-    let file = std::fs::File::open(path)?;
-    let mut rdr = csv::Reader::from_reader(file);
-    let mut records = Vec::new();
-
-    for result in rdr.deserialize() {
-        let record: Record = result?;
-        records.push(record);
-    }
-
-    Ok(records)
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-
-    let bot_ids = Arc::new(args.bots);
-
-    let records = parse_csv(args.log_file).expect("Error while reading in records");
-    let records = Arc::new(records);
-
-    let handles: Vec<_> = args
-        .stats
-        .into_iter()
-        .map(|stat| {
-            let records = Arc::clone(&records);
-            let bot_ids = Arc::clone(&bot_ids);
-
-            thread::spawn(move || {
-                let mut fg = Figure::new();
-                let (x_label, y_label) = stat.labels();
-
-                let mut axes = fg
-                    .axes2d()
-                    .set_title(stat.title(), &Vec::new())
-                    .set_x_label(x_label, &Vec::new())
-                    .set_y_label(y_label, &Vec::new());
-
-                for (name, x, y) in stat.data(&records, &bot_ids) {
-                    axes = axes.lines(&x, &y, &[Caption(name.as_str()), LineWidth(2.0)]);
+            Output::File {
+                name,
+                extension,
+                contents,
+            } => match &args.plot.output_dir {
+                Some(output_dir) => {
+                    let path = output_dir.join(format!("{}.{}", name, extension));
+                    fs::write(&path, contents)?;
+                    println!("Saved {}", path.display());
                 }
-
-                let _ = fg.show();
-            })
-        })
-        .collect();
-
-    for handle in handles {
-        handle.join().unwrap();
+                None => eprintln!("Not writing {}.{} without --output-dir", name, extension),
+            },
+        }
     }
 
     Ok(())
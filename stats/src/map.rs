@@ -0,0 +1,80 @@
+//! Map configurations (`configurations/maps/*.json`) as read by `Simulation::SimulationConfig`.
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Map {
+    pub width: f64,
+    pub height: f64,
+    pub bots: Vec<MapObject>,
+    pub obstacles: Vec<MapObject>,
+    pub station: MapObject,
+    pub target_station: MapObject,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapObject {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub mass: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default)]
+    pub data: Option<u32>,
+}
+
+impl MapObject {
+    /// Corners in map coordinates, rotated by `rotation` degrees around the center like
+    /// `Physics::Shape::Image#corners`.
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        let (half_width, half_height) = (self.width / 2.0, self.height / 2.0);
+        let center = (self.x + half_width, self.y + half_height);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+
+        [
+            (-half_width, -half_height),
+            (half_width, -half_height),
+            (half_width, half_height),
+            (-half_width, half_height),
+        ]
+        .map(|(x, y)| (x * cos - y * sin + center.0, x * sin + y * cos + center.1))
+    }
+
+    /// Whether the point `(x, y)` in map coordinates lies inside the rotated object.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (half_width, half_height) = (self.width / 2.0, self.height / 2.0);
+        let (dx, dy) = (x - self.x - half_width, y - self.y - half_height);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+
+        (dx * cos + dy * sin).abs() <= half_width && (dy * cos - dx * sin).abs() <= half_height
+    }
+}
+
+impl Map {
+    pub fn load(path: &str) -> Result<Map, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// The walls the simulator adds around every map, see `SimulationConfig#create_borders`.
+    pub fn borders(&self) -> Vec<MapObject> {
+        let wall = |x, y, width, height| MapObject {
+            x,
+            y,
+            width,
+            height,
+            mass: 500.0,
+            rotation: 0.0,
+            data: None,
+        };
+
+        vec![
+            wall(0.0, 0.0, 10.0, self.height),
+            wall(self.width - 10.0, 0.0, 10.0, self.height),
+            wall(0.0, 0.0, self.width, 10.0),
+            wall(0.0, self.height - 10.0, self.width, 10.0),
+        ]
+    }
+}
@@ -0,0 +1,384 @@
+//! Metrics computed from a replay log, and the registry `capbot-stats` picks them from.
+//!
+//! A metric only sees the log frame by frame through [`Metric::accumulate`], so every metric can be
+//! computed in the same single pass of the [`Engine`](crate::engine::Engine). Metrics defined
+//! outside this crate become available on the command line by registering them on a [`Registry`],
+//! together with the `clap::Args` of their own options.
+
+use std::any::TypeId;
+
+use crate::aggregate;
+use crate::cohesion;
+use crate::encounter::{self, EncounterArgs};
+use crate::exploration;
+use crate::ledger::{self, LedgerArgs};
+use crate::map::Map;
+use crate::movement::{self, MovementArgs};
+use crate::network;
+use crate::occupancy::{self, OccupancyArgs};
+use crate::plot::Plot;
+use crate::propagation;
+use crate::provenance;
+use crate::replay::Frame;
+use crate::speed;
+use crate::stuck::{self, StuckArgs};
+use crate::survival::{self, SurvivalArgs};
+use crate::table::Table;
+use crate::time::TimeBase;
+use crate::transition;
+
+/// Settings shared by every metric, taken from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub bot_ids: Vec<u16>,
+    pub time: TimeBase,
+    /// Map the log was recorded on, for metrics that draw or measure against it.
+    pub map: Option<Map>,
+    /// The parsed command line, holding the options registered with [`Registry::options`].
+    pub matches: Option<clap::ArgMatches>,
+}
+
+impl Options {
+    /// Options of type `T` from the command line, or their defaults without one. `T` must have been
+    /// registered with [`Registry::options`].
+    pub fn args<T: clap::FromArgMatches + Default>(&self) -> T {
+        match &self.matches {
+            Some(matches) => T::from_arg_matches(matches).unwrap_or_else(|err| err.exit()),
+            None => T::default(),
+        }
+    }
+}
+
+/// Something a metric produces, written to `<output-dir>/<name>.<ext>`.
+#[derive(Debug, Clone)]
+pub enum Output {
+    Plot {
+        name: String,
+        plot: Plot,
+    },
+    /// Printed to the terminal, and written as CSV.
+    Table {
+        name: String,
+        table: Table,
+    },
+    /// Written as is, only when there is an output directory.
+    File {
+        name: String,
+        extension: &'static str,
+        contents: String,
+    },
+}
+
+impl Output {
+    pub fn plot(name: &str, plot: Plot) -> Self {
+        Output::Plot {
+            name: name.to_string(),
+            plot,
+        }
+    }
+
+    pub fn table(name: &str, table: Table) -> Self {
+        Output::Table {
+            name: name.to_string(),
+            table,
+        }
+    }
+
+    pub fn file(name: &str, extension: &'static str, contents: String) -> Self {
+        Output::File {
+            name: name.to_string(),
+            extension,
+            contents,
+        }
+    }
+}
+
+pub trait Metric: Send {
+    /// Name used to select the metric with `--stats`.
+    fn name(&self) -> &'static str;
+    /// Title of the main plot, metrics that only make tables have none.
+    fn title(&self) -> String {
+        String::new()
+    }
+    /// X and Y axis labels of the main plot.
+    fn labels(&self) -> (String, String) {
+        (String::new(), String::new())
+    }
+    fn accumulate(&mut self, frame: &Frame);
+    fn finish(self: Box<Self>) -> Vec<Output>;
+}
+
+pub type Constructor = fn(&Options) -> Box<dyn Metric>;
+
+/// Adds options to a command line, like [`clap::Args::augment_args`].
+pub type Augment = fn(clap::Command) -> clap::Command;
+
+pub struct Registry {
+    /// Name, constructor and whether the metric runs when `--stats` is not given.
+    entries: Vec<(&'static str, Constructor, bool)>,
+    /// Options of the metrics, each type once.
+    options: Vec<(TypeId, Augment)>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.options::<OccupancyArgs>();
+        registry.options::<EncounterArgs>();
+        registry.options::<LedgerArgs>();
+        registry.options::<SurvivalArgs>();
+        registry.options::<MovementArgs>();
+        registry.options::<StuckArgs>();
+
+        registry.register("data-per-bot", |options| {
+            Box::new(aggregate::DataPerBot::new(options))
+        });
+        registry.register("energy-per-bot", |options| {
+            Box::new(aggregate::EnergyPerBot::new(options))
+        });
+        registry.register("status-per-bot", |options| {
+            Box::new(aggregate::StatusPerBot::new(options))
+        });
+        registry.register("data-cumulative", |options| {
+            Box::new(aggregate::DataCumulative::new(options))
+        });
+        registry.register("energy-cumulative", |options| {
+            Box::new(aggregate::EnergyCumulative::new(options))
+        });
+        registry.register("locations", |options| {
+            Box::new(aggregate::Locations::new(options))
+        });
+        registry.register_opt_in("status-composition", |options| {
+            Box::new(aggregate::StatusComposition::new(options))
+        });
+        registry.register_opt_in("occupancy", |options| {
+            Box::new(occupancy::Occupancy::new(options))
+        });
+        registry.register_opt_in("encounters", |options| {
+            Box::new(encounter::Encounters::new(options))
+        });
+        registry.register_opt_in("contact-network", |options| {
+            Box::new(network::ContactNetwork::new(options))
+        });
+        registry.register_opt_in("data-propagation", |options| {
+            Box::new(propagation::DataPropagation::new(options))
+        });
+        registry.register_opt_in("data-provenance", |options| {
+            Box::new(provenance::DataProvenance::new(options))
+        });
+        registry.register_opt_in("energy-ledger", |options| {
+            Box::new(ledger::EnergyLedger::new(options))
+        });
+        registry.register_opt_in("status-transitions", |options| {
+            Box::new(transition::StatusTransitions::new(options))
+        });
+        registry.register_opt_in("survival", |options| {
+            Box::new(survival::Survival::new(options))
+        });
+        registry.register_opt_in("speed", |options| Box::new(speed::Speed::new(options)));
+        registry.register_opt_in("cohesion", |options| {
+            Box::new(cohesion::Cohesion::new(options))
+        });
+        registry.register_opt_in("movement", |options| {
+            Box::new(movement::Movement::new(options))
+        });
+        registry.register_opt_in("distance", |options| {
+            Box::new(exploration::Distance::new(options))
+        });
+        registry.register_opt_in("coverage", |options| {
+            Box::new(exploration::Coverage::new(options))
+        });
+        registry.register_opt_in("stuck", |options| Box::new(stuck::Stuck::new(options)));
+
+        registry
+    }
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Registry {
+            entries: Vec::new(),
+            options: Vec::new(),
+        }
+    }
+
+    /// Adds the options `T` to the command line, for metrics to read with [`Options::args`].
+    /// Options shared by several metrics only need to be added once.
+    pub fn options<T: clap::Args + 'static>(&mut self) {
+        if !self.options.iter().any(|(id, _)| *id == TypeId::of::<T>()) {
+            self.options.push((TypeId::of::<T>(), T::augment_args));
+        }
+    }
+
+    /// `command` with the options of the metrics added.
+    pub fn augment(&self, command: clap::Command) -> clap::Command {
+        self.options
+            .iter()
+            .fold(command, |command, (_, augment)| augment(command))
+    }
+
+    /// Registers a metric under `name`, which should be its [`Metric::name`], replacing any metric
+    /// of the same name.
+    pub fn register(&mut self, name: &'static str, constructor: Constructor) {
+        self.insert(name, constructor, true);
+    }
+
+    /// Registers a metric that only runs when it is asked for with `--stats`.
+    pub fn register_opt_in(&mut self, name: &'static str, constructor: Constructor) {
+        self.insert(name, constructor, false);
+    }
+
+    fn insert(&mut self, name: &'static str, constructor: Constructor, default: bool) {
+        match self.entries.iter_mut().find(|(n, _, _)| *n == name) {
+            Some(entry) => *entry = (name, constructor, default),
+            None => self.entries.push((name, constructor, default)),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries.iter().map(|(name, _, _)| *name).collect()
+    }
+
+    /// Names of the metrics that run by default.
+    pub fn defaults(&self) -> Vec<&'static str> {
+        self.entries
+            .iter()
+            .filter(|(_, _, default)| *default)
+            .map(|(name, _, _)| *name)
+            .collect()
+    }
+
+    pub fn build(&self, name: &str, options: &Options) -> Option<Box<dyn Metric>> {
+        self.entries
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, constructor, _)| constructor(options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    /// A table-only metric that reports the option it was built with.
+    struct Probe {
+        name: &'static str,
+        distance: f64,
+    }
+
+    impl Metric for Probe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn accumulate(&mut self, _frame: &Frame) {}
+
+        fn finish(self: Box<Self>) -> Vec<Output> {
+            let mut table = Table::new("Probe", &["Distance"]);
+            table.push(vec![self.distance.to_string()]);
+            vec![Output::table(self.name, table)]
+        }
+    }
+
+    fn probe(options: &Options) -> Box<dyn Metric> {
+        Box::new(Probe {
+            name: "probe",
+            distance: options.args::<EncounterArgs>().contact_distance,
+        })
+    }
+
+    fn other(_options: &Options) -> Box<dyn Metric> {
+        Box::new(Probe {
+            name: "other",
+            distance: 0.0,
+        })
+    }
+
+    fn distance(metric: Box<dyn Metric>) -> String {
+        match &metric.finish()[0] {
+            Output::Table { table, .. } => table.rows[0][0].clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[derive(clap::Parser)]
+    struct Cli {
+        #[arg(long)]
+        log_file: String,
+    }
+
+    #[test]
+    fn registers_metrics_in_order() {
+        let mut registry = Registry::empty();
+        registry.register("probe", probe);
+        registry.register_opt_in("other", other);
+        registry.register("extra", other);
+
+        assert_eq!(registry.names(), vec!["probe", "other", "extra"]);
+        assert_eq!(registry.defaults(), vec!["probe", "extra"]);
+        assert!(registry.build("missing", &Options::default()).is_none());
+    }
+
+    #[test]
+    fn registering_a_name_again_replaces_the_metric() {
+        let mut registry = Registry::empty();
+        registry.register("probe", other);
+        registry.register("next", other);
+        registry.register_opt_in("probe", probe);
+
+        assert_eq!(registry.names(), vec!["probe", "next"]);
+        assert_eq!(registry.defaults(), vec!["next"]);
+        let metric = registry.build("probe", &Options::default()).unwrap();
+        assert_eq!(metric.name(), "probe");
+        assert_eq!(metric.title(), "");
+    }
+
+    #[test]
+    fn metrics_read_their_options_from_the_command_line() {
+        let mut registry = Registry::empty();
+        registry.register("probe", probe);
+        registry.options::<EncounterArgs>();
+        registry.options::<EncounterArgs>();
+
+        let command = registry.augment(Cli::command());
+        let matches = command
+            .try_get_matches_from([
+                "capbot-stats",
+                "--log-file",
+                "log.csv",
+                "--contact-distance",
+                "25",
+            ])
+            .unwrap();
+        assert_eq!(Cli::from_arg_matches(&matches).unwrap().log_file, "log.csv");
+
+        let options = Options {
+            matches: Some(matches),
+            ..Default::default()
+        };
+        assert_eq!(distance(registry.build("probe", &options).unwrap()), "25");
+        assert_eq!(
+            distance(registry.build("probe", &Options::default()).unwrap()),
+            "10"
+        );
+    }
+
+    #[test]
+    fn default_registry_parses_every_option() {
+        let registry = Registry::default();
+        let matches = registry
+            .augment(Cli::command())
+            .try_get_matches_from(["capbot-stats", "--log-file", "log.csv"])
+            .unwrap();
+        let options = Options {
+            matches: Some(matches),
+            ..Default::default()
+        };
+
+        for name in registry.names() {
+            assert_eq!(registry.build(name, &options).unwrap().name(), name);
+        }
+    }
+}
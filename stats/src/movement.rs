@@ -0,0 +1,867 @@
+//! How bots search the map, in the terms of movement ecology.
+//!
+//! A bot drives straight until its strategy or a collision turns it, so its track splits into steps:
+//! runs at one heading, ended by a turn or by stopping. Steps cut short by the start or end of the
+//! log, or by frames the bot is missing from, are left out. The mean squared displacement over
+//! growing lags tells diffusive (exponent 1) from ballistic (exponent 2) search, and the step lengths
+//! are fitted with a power law (Lévy walk) and an exponential distribution.
+//!
+//! Displacements are summed as the log is read, so only the samples within the longest lag of the
+//! last one are kept per bot.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::f64::consts::LN_10;
+
+use crate::aggregate::selected;
+use crate::metric::{Metric, Options, Output};
+use crate::plot::{Bin, Color, Layer, Plot, Series};
+use crate::replay::Frame;
+use crate::table::Table;
+use crate::time::TimeBase;
+
+/// Lags of the mean squared displacement per decade.
+const LAGS_PER_DECADE: f64 = 10.0;
+
+/// Bins of the turning angle distribution, 15 degrees each.
+const TURN_BINS: usize = 24;
+
+// Options of the movement analysis (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq)]
+pub struct MovementArgs {
+    /// Length in seconds of the stretches of track the straightness is measured over
+    #[arg(long, default_value_t = 10.0)]
+    pub straightness_window: f64,
+    /// Smallest change of heading in degrees that ends a step
+    #[arg(long, default_value_t = 1.0)]
+    pub turn_threshold: f64,
+    /// Shortest step in pixels the step length distribution is fitted to
+    #[arg(long, default_value_t = 1.0)]
+    pub min_step: f64,
+    /// Longest lag in seconds of the mean squared displacement
+    #[arg(long, default_value_t = 300.0)]
+    pub max_lag: f64,
+}
+
+impl Default for MovementArgs {
+    fn default() -> Self {
+        MovementArgs {
+            straightness_window: 10.0,
+            turn_threshold: 1.0,
+            min_step: 1.0,
+            max_lag: 300.0,
+        }
+    }
+}
+
+/// Signed change from heading `from` to heading `to` in degrees, in `[-180, 180)`.
+pub(crate) fn turn(from: f64, to: f64) -> f64 {
+    (to - from + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Lags in samples, spaced evenly on a log scale up to `longest`.
+fn lags(longest: usize) -> Vec<usize> {
+    let mut lags: Vec<usize> = Vec::new();
+    let mut lag = 1.0;
+    while lag <= longest as f64 {
+        if lags.last() != Some(&(lag.round() as usize)) {
+            lags.push(lag.round() as usize);
+        }
+        lag *= 10f64.powf(1.0 / LAGS_PER_DECADE);
+    }
+
+    lags
+}
+
+/// Least-squares line through `points`, as slope, intercept and coefficient of determination.
+fn fit_line(points: &[(f64, f64)]) -> Option<(f64, f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let syy: f64 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let r2 = if syy == 0.0 {
+        1.0
+    } else {
+        sxy * sxy / (sxx * syy)
+    };
+    Some((slope, mean_y - slope * mean_x, r2))
+}
+
+/// Ticks at the powers of ten between `min` and `max`, of an axis showing base-10 logarithms.
+fn log_ticks(min: f64, max: f64) -> Vec<(f64, String)> {
+    (min.floor() as i32..=max.ceil() as i32)
+        .map(|power| (power as f64, format!("{}", 10f64.powi(power))))
+        .collect()
+}
+
+/// Sets the range of an axis showing base-10 logarithms to whole decades, with a tick at each.
+fn log_axis(plot: &mut Plot, x: (f64, f64), y: (f64, f64)) {
+    plot.x.min = Some(x.0.floor());
+    plot.x.max = Some(x.1.ceil());
+    plot.x.ticks = log_ticks(x.0, x.1);
+    plot.y.min = Some(y.0.floor());
+    plot.y.max = Some(y.1.ceil());
+    plot.y.ticks = log_ticks(y.0, y.1);
+}
+
+/// Maximum likelihood fits of the step lengths at least `min` pixels long.
+#[derive(Debug, Clone, Copy)]
+struct Fits {
+    min: f64,
+    steps: usize,
+    /// Exponent of the power law `p(x) ~ x^-mu`.
+    mu: f64,
+    /// Rate of the exponential `p(x) ~ e^(-lambda x)`, per pixel.
+    lambda: f64,
+    power_likelihood: f64,
+    exponential_likelihood: f64,
+}
+
+impl Fits {
+    fn new(steps: &[f64], min: f64) -> Option<Self> {
+        let steps: Vec<f64> = steps.iter().copied().filter(|step| *step >= min).collect();
+        let n = steps.len() as f64;
+        let logs: f64 = steps.iter().map(|step| (step / min).ln()).sum();
+        let excess: f64 = steps.iter().map(|step| step - min).sum();
+        if logs == 0.0 || excess == 0.0 {
+            return None;
+        }
+
+        let mu = 1.0 + n / logs;
+        let lambda = n / excess;
+        Some(Fits {
+            min,
+            steps: steps.len(),
+            mu,
+            lambda,
+            power_likelihood: n * (mu - 1.0).ln() - n * min.ln() - mu * logs,
+            exponential_likelihood: n * lambda.ln() - lambda * excess,
+        })
+    }
+
+    /// Base-10 logarithm of the fraction of steps at least `step` long under each fit.
+    fn survival(&self, step: f64) -> (f64, f64) {
+        (
+            (1.0 - self.mu) * (step / self.min).log10(),
+            -self.lambda * (step - self.min) / LN_10,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    tick: u64,
+    x: f64,
+    y: f64,
+}
+
+/// A step in progress.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    heading: f64,
+    length: f64,
+    /// Whether the bot was already moving at the first sample of its segment, so the step began
+    /// before it.
+    truncated: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bot {
+    last_frame: Option<usize>,
+    /// Samples since the last frame the bot was missing from, at most the longest lag old.
+    recent: VecDeque<Point>,
+    /// Samples since the last frame the bot was missing from, and the most of any such stretch.
+    samples: usize,
+    longest: usize,
+    /// Sums of the squared displacements and of the lags in ticks, and their count, per lag of
+    /// [`Movement::lags`].
+    displacements: Vec<(f64, f64, usize)>,
+    /// First sample and path length of the stretch the straightness is measured over.
+    stretch: Option<(Point, f64)>,
+    /// Net displacement over path length of every stretch the bot moved in.
+    straightness: Vec<f64>,
+    run: Option<Run>,
+    /// Heading of the last finished step, to measure the turn to the next one.
+    heading: Option<f64>,
+    steps: Vec<f64>,
+    turns: Vec<f64>,
+}
+
+impl Bot {
+    fn stop(&mut self) {
+        if let Some(run) = self.run.take() {
+            if !run.truncated {
+                self.steps.push(run.length);
+            }
+            self.heading = Some(run.heading);
+        }
+    }
+
+    /// Adds the displacements to `point` from the recent samples `lags` before it.
+    fn displace(&mut self, point: Point, lags: &[usize]) {
+        if self.displacements.len() < lags.len() {
+            self.displacements.resize(lags.len(), (0.0, 0.0, 0));
+        }
+        for (lag, sums) in lags.iter().zip(&mut self.displacements) {
+            let Some(a) = self.recent.len().checked_sub(*lag).map(|i| self.recent[i]) else {
+                break;
+            };
+            sums.0 += (point.x - a.x).powi(2) + (point.y - a.y).powi(2);
+            sums.1 += (point.tick - a.tick) as f64;
+            sums.2 += 1;
+        }
+    }
+
+    /// Ends the stretch at `point` once it spans `window` ticks.
+    fn stretch(&mut self, point: Point, distance: f64, window: f64) {
+        let Some((start, path)) = &mut self.stretch else {
+            self.stretch = Some((point, 0.0));
+            return;
+        };
+        *path += distance;
+        if (point.tick - start.tick) as f64 >= window {
+            if *path > 0.0 {
+                self.straightness
+                    .push((point.x - start.x).hypot(point.y - start.y) / *path);
+            }
+            self.stretch = Some((point, 0.0));
+        }
+    }
+}
+
+/// Mean squared displacement in px² and lag in seconds per lag, from the sums of
+/// [`Bot::displacements`].
+fn msd(sums: &[(f64, f64, usize)], time: &TimeBase) -> Vec<(f64, f64)> {
+    sums.iter()
+        .filter(|(squares, _, count)| *count > 0 && *squares > 0.0)
+        .map(|(squares, ticks, count)| {
+            (time.seconds(ticks / *count as f64), squares / *count as f64)
+        })
+        .collect()
+}
+
+/// Exponent and coefficient of determination of `msd ~ lag^alpha`.
+fn exponent(msd: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let logs: Vec<(f64, f64)> = msd
+        .iter()
+        .map(|(lag, msd)| (lag.log10(), msd.log10()))
+        .collect();
+    fit_line(&logs).map(|(slope, _, r2)| (slope, r2))
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+#[derive(Default)]
+pub struct Movement {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    args: MovementArgs,
+    frames: usize,
+    /// Most recent samples a new sample was paired with, and the lags in samples up to it.
+    kept: usize,
+    lags: Vec<usize>,
+    bots: BTreeMap<u16, Bot>,
+}
+
+impl Movement {
+    pub fn new(options: &Options) -> Self {
+        Movement {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            args: options.args(),
+            ..Default::default()
+        }
+    }
+
+    /// Number of lags up to a quarter of the longest stretch of track.
+    fn lag_count(&self) -> usize {
+        let longest = self.bots.values().map(|bot| bot.longest).max().unwrap_or(0);
+        self.lags.partition_point(|lag| *lag <= longest / 4)
+    }
+
+    fn summary(&self, msds: &[(String, Vec<(f64, f64)>)]) -> Table {
+        let mut table = Table::new(
+            "Movement per Bot",
+            &[
+                "Bot",
+                "Steps",
+                "Mean Step (px)",
+                "Mean Turn (°)",
+                "Straightness",
+                "Tortuosity",
+                "MSD Exponent",
+                "MSD Fit R²",
+            ],
+        );
+
+        let mut rows: Vec<(Vec<f64>, Vec<f64>, Vec<f64>)> = self
+            .bots
+            .values()
+            .map(|bot| {
+                (
+                    bot.steps.clone(),
+                    bot.turns.clone(),
+                    bot.straightness.clone(),
+                )
+            })
+            .collect();
+        let swarm = rows.iter().fold(
+            (Vec::new(), Vec::new(), Vec::new()),
+            |mut swarm, (steps, turns, straightness)| {
+                swarm.0.extend(steps);
+                swarm.1.extend(turns);
+                swarm.2.extend(straightness);
+                swarm
+            },
+        );
+        rows.push(swarm);
+
+        for ((name, msd), (steps, turns, straightness)) in msds.iter().zip(&rows) {
+            let format = |value: Option<f64>, precision: usize| {
+                value.map_or("-".to_string(), |value| format!("{:.*}", precision, value))
+            };
+            let turns: Vec<f64> = turns.iter().map(|turn| turn.abs()).collect();
+            let straightness = mean(straightness);
+            let exponent = exponent(msd);
+
+            table.push(vec![
+                name.clone(),
+                steps.len().to_string(),
+                format(mean(steps), 2),
+                format(mean(&turns), 1),
+                format(straightness, 3),
+                format(straightness.map(|s| 1.0 / s), 3),
+                format(exponent.map(|(alpha, _)| alpha), 3),
+                format(exponent.map(|(_, r2)| r2), 3),
+            ]);
+        }
+
+        table
+    }
+
+    fn steps(&self) -> Vec<f64> {
+        self.bots
+            .values()
+            .flat_map(|bot| bot.steps.iter().copied())
+            .collect()
+    }
+
+    fn fits_table(&self, fits: Option<Fits>) -> Table {
+        let mut table = Table::new(
+            "Step Length Fits",
+            &[
+                "Model",
+                "Parameter",
+                "Value",
+                "Min Step (px)",
+                "Steps",
+                "Log-Likelihood",
+            ],
+        );
+
+        if let Some(fits) = fits {
+            for (model, parameter, value, likelihood) in [
+                ("Power Law", "mu", fits.mu, fits.power_likelihood),
+                (
+                    "Exponential",
+                    "lambda (1/px)",
+                    fits.lambda,
+                    fits.exponential_likelihood,
+                ),
+            ] {
+                table.push(vec![
+                    model.to_string(),
+                    parameter.to_string(),
+                    format!("{:.4}", value),
+                    format!("{:.2}", fits.min),
+                    fits.steps.to_string(),
+                    format!("{:.2}", likelihood),
+                ]);
+            }
+        }
+
+        table
+    }
+
+    /// Fraction of steps at least as long as each step, with the fitted distributions, log-log.
+    fn steps_plot(&self, fits: Option<Fits>) -> Plot {
+        let mut plot = Plot::new(
+            "Step Length Distribution",
+            (
+                "Step Length (px, log scale)",
+                "Fraction of Steps at Least as Long (log scale)",
+            ),
+        );
+
+        let mut steps: Vec<f64> = self
+            .steps()
+            .into_iter()
+            .filter(|step| *step >= self.args.min_step)
+            .collect();
+        steps.sort_by(f64::total_cmp);
+        let (Some(fits), Some(longest)) = (fits, steps.last().copied()) else {
+            return plot;
+        };
+
+        let n = steps.len() as f64;
+        plot.scatter(Series {
+            name: "Steps".to_string(),
+            x: steps.iter().map(|step| step.log10()).collect(),
+            y: (0..steps.len())
+                .map(|i| ((n - i as f64) / n).log10())
+                .collect(),
+        });
+
+        let lengths: Vec<f64> = (0..=50)
+            .map(|i| fits.min * (longest / fits.min).powf(i as f64 / 50.0))
+            .collect();
+        let lowest = (1.0 / n).log10();
+        for (name, color, survival) in [
+            (
+                format!("Power Law (mu = {:.2})", fits.mu),
+                Color::RED,
+                (|fits: &Fits, step| fits.survival(step).0) as fn(&Fits, f64) -> f64,
+            ),
+            (
+                format!("Exponential (lambda = {:.3}/px)", fits.lambda),
+                Color::BLUE,
+                |fits: &Fits, step| fits.survival(step).1,
+            ),
+        ] {
+            let (x, y): (Vec<f64>, Vec<f64>) = lengths
+                .iter()
+                .map(|step| (step.log10(), survival(&fits, *step)))
+                .filter(|(_, y)| *y >= lowest)
+                .unzip();
+            plot.layers.push(Layer::Line {
+                series: Series { name, x, y },
+                color: Some(color),
+                width: 2.0,
+            });
+        }
+
+        log_axis(
+            &mut plot,
+            (fits.min.log10(), longest.log10()),
+            (lowest, 0.0),
+        );
+        plot
+    }
+
+    fn turns_plot(&self) -> Plot {
+        let mut plot = Plot::new(
+            "Turning Angle Distribution",
+            ("Turning Angle (°, left positive)", "Fraction of Turns"),
+        );
+        plot.legend = false;
+        plot.x.min = Some(-180.0);
+        plot.x.max = Some(180.0);
+        plot.x.ticks = [-180, -90, 0, 90, 180]
+            .iter()
+            .map(|angle| (*angle as f64, angle.to_string()))
+            .collect();
+        plot.y.min = Some(0.0);
+
+        let turns: Vec<f64> = self
+            .bots
+            .values()
+            .flat_map(|bot| bot.turns.iter().copied())
+            .collect();
+        if turns.is_empty() {
+            return plot;
+        }
+
+        let width = 360.0 / TURN_BINS as f64;
+        let mut counts = [0usize; TURN_BINS];
+        for turn in &turns {
+            counts[(((turn + 180.0) / width) as usize).min(TURN_BINS - 1)] += 1;
+        }
+        plot.histogram(
+            "Turns",
+            counts
+                .iter()
+                .enumerate()
+                .map(|(i, count)| Bin {
+                    start: -180.0 + i as f64 * width,
+                    end: -180.0 + (i + 1) as f64 * width,
+                    value: *count as f64 / turns.len() as f64,
+                })
+                .collect(),
+        );
+
+        plot
+    }
+}
+
+impl Metric for Movement {
+    fn name(&self) -> &'static str {
+        "movement"
+    }
+
+    fn title(&self) -> String {
+        "Mean Squared Displacement".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (
+            "Lag (s, log scale)".to_string(),
+            "Mean Squared Displacement (px², log scale)".to_string(),
+        )
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let max_lag = self.time.ticks(self.args.max_lag);
+        let window = self.time.ticks(self.args.straightness_window);
+
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            let bot = self.bots.entry(record.bot_id).or_default();
+            let point = Point {
+                tick: frame.tick,
+                x: record.x,
+                y: record.y,
+            };
+            let previous = match bot.last_frame {
+                Some(last) if last + 1 == self.frames => bot.recent.back().copied(),
+                _ => {
+                    bot.recent.clear();
+                    bot.samples = 0;
+                    bot.stretch = None;
+                    bot.run = None;
+                    bot.heading = None;
+                    None
+                }
+            };
+            bot.last_frame = Some(self.frames);
+
+            let moving = record.vel_x != 0.0 || record.vel_y != 0.0;
+            // `rotation` is clockwise on the screen, so left turns come out positive.
+            let heading = 360.0 - record.rotation;
+            let distance = previous.map_or(0.0, |p| (point.x - p.x).hypot(point.y - p.y));
+
+            while bot
+                .recent
+                .front()
+                .is_some_and(|first| (point.tick - first.tick) as f64 > max_lag)
+            {
+                bot.recent.pop_front();
+            }
+            if bot.recent.len() > self.kept {
+                self.kept = bot.recent.len();
+                self.lags = lags(self.kept);
+            }
+            bot.displace(point, &self.lags);
+            bot.recent.push_back(point);
+            bot.samples += 1;
+            bot.longest = bot.longest.max(bot.samples);
+            bot.stretch(point, distance, window);
+
+            // The distance since the last sample was driven at the heading of the running step.
+            if let Some(run) = &mut bot.run {
+                run.length += distance;
+                if !moving || turn(run.heading, heading).abs() >= self.args.turn_threshold {
+                    bot.stop();
+                }
+            }
+            if moving && bot.run.is_none() {
+                if let Some(last) = bot.heading {
+                    bot.turns.push(turn(last, heading));
+                }
+                bot.run = Some(Run {
+                    heading,
+                    length: 0.0,
+                    truncated: previous.is_none(),
+                });
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let lags = self.lag_count();
+        let mut swarm = vec![(0.0, 0.0, 0); lags];
+        let mut msds = Vec::new();
+        for (bot_id, bot) in &self.bots {
+            let sums = &bot.displacements[..lags.min(bot.displacements.len())];
+            for (total, sum) in swarm.iter_mut().zip(sums) {
+                total.0 += sum.0;
+                total.1 += sum.1;
+                total.2 += sum.2;
+            }
+            msds.push((bot_id.to_string(), msd(sums, &self.time)));
+        }
+        msds.push(("Swarm".to_string(), msd(&swarm, &self.time)));
+
+        let (x, y) = self.labels();
+        let mut plot = Plot::new(&self.title(), (&x, &y));
+        for (name, msd) in &msds {
+            let series = Series {
+                name: if name == "Swarm" {
+                    name.clone()
+                } else {
+                    format!("Bot {}", name)
+                },
+                x: msd.iter().map(|(lag, _)| lag.log10()).collect(),
+                y: msd.iter().map(|(_, msd)| msd.log10()).collect(),
+            };
+            if name == "Swarm" {
+                plot.layers.push(Layer::Line {
+                    series,
+                    color: Some(Color::BLACK),
+                    width: 3.0,
+                });
+            } else {
+                plot.line(series);
+            }
+        }
+        let logs = msds
+            .iter()
+            .flat_map(|(_, msd)| msd.iter().map(|(lag, msd)| (lag.log10(), msd.log10())));
+        if let (Some(x), Some(y)) = (
+            logs.clone()
+                .map(|(x, _)| (x, x))
+                .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))),
+            logs.map(|(_, y)| (y, y))
+                .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))),
+        ) {
+            log_axis(&mut plot, x, y);
+        }
+
+        let fits = Fits::new(&self.steps(), self.args.min_step);
+        vec![
+            Output::table(self.name(), self.summary(&msds)),
+            Output::table(&format!("{}-steps", self.name()), self.fits_table(fits)),
+            Output::plot(self.name(), plot),
+            Output::plot(&format!("{}-steps", self.name()), self.steps_plot(fits)),
+            Output::plot(&format!("{}-turns", self.name()), self.turns_plot()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{BotStatus, ObjectType, Record};
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Evenly spread quantiles of a distribution, a sample without random noise.
+    fn quantiles(inverse: impl Fn(f64) -> f64) -> Vec<f64> {
+        (0..1000)
+            .map(|i| inverse((i as f64 + 0.5) / 1000.0))
+            .collect()
+    }
+
+    #[test]
+    fn turns_take_the_short_way_round() {
+        assert_eq!(turn(10.0, 30.0), 20.0);
+        assert_eq!(turn(30.0, 10.0), -20.0);
+        assert_eq!(turn(350.0, 10.0), 20.0);
+        assert_eq!(turn(10.0, 350.0), -20.0);
+        assert_eq!(turn(0.0, 180.0), -180.0);
+        assert_eq!(turn(90.0, 90.0), 0.0);
+    }
+
+    #[test]
+    fn fits_a_line() {
+        // y = 2x + 1 exactly.
+        let (slope, intercept, r2) = fit_line(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+        assert_close(slope, 2.0, 1e-12);
+        assert_close(intercept, 1.0, 1e-12);
+        assert_close(r2, 1.0, 1e-12);
+
+        // Means 1.5 and 4, Sxx = 5, Sxy = 8 and Syy = 16: slope 8 / 5, r2 = 64 / (5 * 16).
+        let (slope, intercept, r2) =
+            fit_line(&[(0.0, 2.0), (1.0, 2.0), (2.0, 6.0), (3.0, 6.0)]).unwrap();
+        assert_close(slope, 1.6, 1e-12);
+        assert_close(intercept, 4.0 - 1.6 * 1.5, 1e-12);
+        assert_close(r2, 0.8, 1e-12);
+
+        assert!(fit_line(&[(1.0, 1.0)]).is_none());
+        assert!(fit_line(&[(1.0, 1.0), (1.0, 2.0)]).is_none());
+    }
+
+    #[test]
+    fn msd_exponent_of_ballistic_motion() {
+        // Straight driving at constant speed: the displacement grows with the lag.
+        let msd: Vec<(f64, f64)> = [1.0, 2.0, 4.0, 8.0]
+            .iter()
+            .map(|lag| (*lag, (3.0 * lag) * (3.0 * lag)))
+            .collect();
+        let (alpha, r2) = exponent(&msd).unwrap();
+        assert_close(alpha, 2.0, 1e-12);
+        assert_close(r2, 1.0, 1e-12);
+    }
+
+    #[test]
+    fn fits_of_a_small_sample() {
+        // Steps 1, 2, 3 and 4 from a minimum of 1: ln(1 * 2 * 3 * 4) = ln 24, 0 + 1 + 2 + 3 = 6.
+        let fits = Fits::new(&[0.5, 1.0, 2.0, 3.0, 4.0], 1.0).unwrap();
+        assert_eq!(fits.steps, 4);
+        assert_close(fits.mu, 1.0 + 4.0 / 24f64.ln(), 1e-12);
+        assert_close(fits.lambda, 4.0 / 6.0, 1e-12);
+        assert_close(
+            fits.power_likelihood,
+            4.0 * (fits.mu - 1.0).ln() - fits.mu * 24f64.ln(),
+            1e-12,
+        );
+        assert_close(
+            fits.exponential_likelihood,
+            4.0 * (4.0f64 / 6.0).ln() - 4.0,
+            1e-12,
+        );
+
+        assert!(Fits::new(&[1.0, 1.0], 1.0).is_none());
+        assert!(Fits::new(&[0.5], 1.0).is_none());
+    }
+
+    #[test]
+    fn recovers_an_exponential_distribution() {
+        let steps = quantiles(|u| 2.0 - (1.0 - u).ln() / 0.5);
+        let fits = Fits::new(&steps, 2.0).unwrap();
+
+        assert_close(fits.lambda, 0.5, 0.005);
+        assert!(fits.exponential_likelihood > fits.power_likelihood);
+        assert_close(fits.survival(2.0).1, 0.0, 1e-12);
+        assert_close(fits.survival(4.0).1, -1.0 / LN_10, 0.01);
+    }
+
+    #[test]
+    fn recovers_a_power_law() {
+        let steps = quantiles(|u| (1.0 - u).powf(-1.0 / 1.5));
+        let fits = Fits::new(&steps, 1.0).unwrap();
+
+        assert_close(fits.mu, 2.5, 0.01);
+        assert!(fits.power_likelihood > fits.exponential_likelihood);
+        assert_close(fits.survival(10.0).0, -1.5, 0.02);
+    }
+
+    #[test]
+    fn spaces_lags_evenly_on_a_log_scale() {
+        assert_eq!(lags(0), Vec::<usize>::new());
+        assert_eq!(lags(5), vec![1, 2, 3, 4]);
+        assert_eq!(lags(30), vec![1, 2, 3, 4, 5, 6, 8, 10, 13, 16, 20, 25]);
+        assert_eq!(lags(30)[..], lags(100)[..12]);
+    }
+
+    #[test]
+    fn keeps_only_the_samples_within_the_longest_lag() {
+        let mut movement = Movement {
+            args: MovementArgs {
+                max_lag: 1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // Driving east at a pixel per sample, a sample every 2 ticks, with a gap after 150.
+        for i in 0..300 {
+            movement.accumulate(&Frame {
+                tick: i * 2,
+                bots: (i != 150)
+                    .then_some(Record {
+                        tick: i * 2,
+                        bot_id: 0,
+                        energy: 100.0,
+                        data: Vec::new(),
+                        x: i as f64,
+                        y: 0.0,
+                        vel_x: 0.5,
+                        vel_y: 0.0,
+                        rotation: 0.0,
+                        status: BotStatus::Active,
+                        color: [0.0; 4],
+                        r#type: ObjectType::Bot,
+                    })
+                    .into_iter()
+                    .collect(),
+                station: None,
+                target_station: None,
+            });
+        }
+
+        let bot = &movement.bots[&0];
+        // A second is 60 ticks, 31 samples.
+        assert_eq!(bot.recent.len(), 31);
+        assert_eq!(movement.kept, 30);
+        assert_eq!(movement.lags.last(), Some(&25));
+        assert_eq!(bot.longest, 150);
+
+        let lags = movement.lag_count();
+        let msd = msd(&bot.displacements[..lags], &movement.time);
+        assert_eq!(msd.len(), movement.lags.len());
+        for (lag, msd) in msd {
+            // Each second is 30 pixels.
+            assert_close(msd, (lag * 30.0).powi(2), 1e-9);
+        }
+        // Pairs with a lag of one sample, less one lost to the gap.
+        assert_eq!(bot.displacements[0].2, 297);
+    }
+
+    #[test]
+    fn leaves_out_steps_cut_short_by_the_log() {
+        let mut movement = Movement::new(&Options::default());
+        // Already driving east at the first sample, then north, then east again until the log ends.
+        let track = [
+            (0.0, 0.0, 0.0, true),
+            (2.0, 0.0, 0.0, true),
+            (4.0, 0.0, 270.0, true),
+            (4.0, -2.0, 270.0, true),
+            (4.0, -4.0, 270.0, true),
+            (4.0, -4.0, 270.0, false),
+            (6.0, -4.0, 0.0, true),
+            (8.0, -4.0, 0.0, true),
+        ];
+        for (i, (x, y, rotation, moving)) in track.into_iter().enumerate() {
+            let speed = if moving { 1.0 } else { 0.0 };
+            movement.accumulate(&Frame {
+                tick: i as u64 * 2,
+                bots: vec![Record {
+                    tick: i as u64 * 2,
+                    bot_id: 0,
+                    energy: 100.0,
+                    data: Vec::new(),
+                    x,
+                    y,
+                    vel_x: speed,
+                    vel_y: 0.0,
+                    rotation,
+                    status: BotStatus::Active,
+                    color: [0.0; 4],
+                    r#type: ObjectType::Bot,
+                }],
+                station: None,
+                target_station: None,
+            });
+        }
+
+        let bot = &movement.bots[&0];
+        assert_eq!(bot.steps, vec![4.0]);
+        assert_eq!(bot.turns, vec![90.0, -90.0]);
+    }
+}
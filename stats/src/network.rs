@@ -0,0 +1,438 @@
+//! The contact network of the swarm: which bots met through trophallaxis, how often and for how
+//! long, exported as DOT and GraphML.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+
+use crate::aggregate::{plot, selected};
+use crate::encounter::{Detector, Partner};
+use crate::metric::{Metric, Options, Output};
+use crate::plot::Series;
+use crate::replay::Frame;
+use crate::table::Table;
+use crate::time::TimeBase;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Edge {
+    pub contacts: usize,
+    /// Total time in contact, in ticks.
+    pub duration: u64,
+}
+
+/// Undirected, time-aggregated contact graph between bots. Edges are keyed by `(low, high)` bot id.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: BTreeSet<u16>,
+    pub edges: BTreeMap<(u16, u16), Edge>,
+}
+
+impl Graph {
+    pub fn add_contact(&mut self, a: u16, b: u16, duration: u64) {
+        self.nodes.extend([a, b]);
+
+        let edge = self.edges.entry((a.min(b), a.max(b))).or_default();
+        edge.contacts += 1;
+        edge.duration += duration;
+    }
+
+    fn neighbours(&self) -> BTreeMap<u16, Vec<u16>> {
+        let mut neighbours: BTreeMap<u16, Vec<u16>> =
+            self.nodes.iter().map(|node| (*node, Vec::new())).collect();
+        for (a, b) in self.edges.keys() {
+            neighbours.entry(*a).or_default().push(*b);
+            neighbours.entry(*b).or_default().push(*a);
+        }
+
+        neighbours
+    }
+
+    /// Number of distinct bots each bot has met.
+    pub fn degree(&self) -> BTreeMap<u16, usize> {
+        self.neighbours()
+            .into_iter()
+            .map(|(node, neighbours)| (node, neighbours.len()))
+            .collect()
+    }
+
+    /// Betweenness centrality of every node on the unweighted graph (Brandes, 2001).
+    pub fn betweenness(&self) -> BTreeMap<u16, f64> {
+        let neighbours = self.neighbours();
+        let mut centrality: BTreeMap<u16, f64> =
+            self.nodes.iter().map(|node| (*node, 0.0)).collect();
+
+        for source in &self.nodes {
+            let mut stack = Vec::new();
+            let mut predecessors: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+            let mut paths: BTreeMap<u16, f64> = BTreeMap::from([(*source, 1.0)]);
+            let mut distance: BTreeMap<u16, usize> = BTreeMap::from([(*source, 0)]);
+
+            let mut queue = VecDeque::from([*source]);
+            while let Some(node) = queue.pop_front() {
+                stack.push(node);
+                for next in &neighbours[&node] {
+                    if !distance.contains_key(next) {
+                        distance.insert(*next, distance[&node] + 1);
+                        queue.push_back(*next);
+                    }
+                    if distance[next] == distance[&node] + 1 {
+                        *paths.entry(*next).or_default() += paths[&node];
+                        predecessors.entry(*next).or_default().push(node);
+                    }
+                }
+            }
+
+            let mut dependency: BTreeMap<u16, f64> = BTreeMap::new();
+            while let Some(node) = stack.pop() {
+                for previous in predecessors.get(&node).into_iter().flatten() {
+                    let share = paths[previous] / paths[&node]
+                        * (1.0 + dependency.get(&node).copied().unwrap_or(0.0));
+                    *dependency.entry(*previous).or_default() += share;
+                }
+                if node != *source {
+                    *centrality.get_mut(&node).expect("Unknown node") +=
+                        dependency.get(&node).copied().unwrap_or(0.0);
+                }
+            }
+        }
+
+        // Every path was counted from both of its ends.
+        centrality.values_mut().for_each(|value| *value /= 2.0);
+        centrality
+    }
+
+    /// Connected components, each sorted and ordered by their lowest bot id.
+    pub fn components(&self) -> Vec<Vec<u16>> {
+        let neighbours = self.neighbours();
+        let mut seen = BTreeSet::new();
+        let mut components = Vec::new();
+
+        for node in &self.nodes {
+            if !seen.insert(*node) {
+                continue;
+            }
+
+            let mut component = vec![*node];
+            let mut queue = VecDeque::from([*node]);
+            while let Some(node) = queue.pop_front() {
+                for next in &neighbours[&node] {
+                    if seen.insert(*next) {
+                        component.push(*next);
+                        queue.push_back(*next);
+                    }
+                }
+            }
+
+            component.sort();
+            components.push(component);
+        }
+
+        components
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph contacts {\n");
+        for node in &self.nodes {
+            writeln!(dot, "    {} [label=\"Bot {}\"];", node, node).unwrap();
+        }
+        for ((a, b), edge) in &self.edges {
+            writeln!(
+                dot,
+                "    {} -- {} [weight={}, contacts={}, duration={}];",
+                a, b, edge.contacts, edge.contacts, edge.duration
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut graphml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"contacts\" for=\"edge\" attr.name=\"contacts\" attr.type=\"int\"/>\n",
+            "  <key id=\"duration\" for=\"edge\" attr.name=\"duration\" attr.type=\"long\"/>\n",
+            "  <graph id=\"contacts\" edgedefault=\"undirected\">\n",
+        ));
+        for node in &self.nodes {
+            writeln!(graphml, "    <node id=\"{}\"/>", node).unwrap();
+        }
+        for ((a, b), edge) in &self.edges {
+            writeln!(graphml, "    <edge source=\"{}\" target=\"{}\">", a, b).unwrap();
+            writeln!(
+                graphml,
+                "      <data key=\"contacts\">{}</data>",
+                edge.contacts
+            )
+            .unwrap();
+            writeln!(
+                graphml,
+                "      <data key=\"duration\">{}</data>",
+                edge.duration
+            )
+            .unwrap();
+            graphml.push_str("    </edge>\n");
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+
+        graphml
+    }
+}
+
+/// Components of the cumulative contact graph as contacts come in, for a plot over time.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Components {
+    parents: BTreeMap<u16, u16>,
+    pub(crate) count: usize,
+}
+
+impl Components {
+    pub(crate) fn new(nodes: &BTreeSet<u16>) -> Self {
+        Components {
+            parents: nodes.iter().map(|node| (*node, *node)).collect(),
+            count: nodes.len(),
+        }
+    }
+
+    fn root(&mut self, node: u16) -> u16 {
+        let parent = self.parents[&node];
+        if parent == node {
+            return node;
+        }
+
+        let root = self.root(parent);
+        self.parents.insert(node, root);
+        root
+    }
+
+    pub(crate) fn join(&mut self, a: u16, b: u16) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            self.parents.insert(a, b);
+            self.count -= 1;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ContactNetwork {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    detector: Detector,
+    bots: BTreeSet<u16>,
+    ticks: Option<(u64, u64)>,
+}
+
+impl ContactNetwork {
+    pub fn new(options: &Options) -> Self {
+        ContactNetwork {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            detector: Detector::new(options.args(), options.map.as_ref()),
+            ..Default::default()
+        }
+    }
+
+    fn table(&self, graph: &Graph) -> Table {
+        let contact_time = format!("Contact Time ({})", self.time.unit.name());
+        let mut table = Table::new(
+            "Contact Network",
+            &[
+                "Bot",
+                "Degree",
+                "Contacts",
+                &contact_time,
+                "Betweenness",
+                "Component",
+            ],
+        );
+
+        let degree = graph.degree();
+        let betweenness = graph.betweenness();
+        let component: BTreeMap<u16, usize> = graph
+            .components()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, nodes)| nodes.into_iter().map(move |node| (node, i + 1)))
+            .collect();
+
+        for node in &graph.nodes {
+            let incident = graph
+                .edges
+                .iter()
+                .filter(|((a, b), _)| a == node || b == node)
+                .map(|(_, edge)| edge);
+            let (contacts, duration) = incident.fold((0, 0), |(contacts, duration), edge| {
+                (contacts + edge.contacts, duration + edge.duration)
+            });
+
+            table.push(vec![
+                node.to_string(),
+                degree[node].to_string(),
+                contacts.to_string(),
+                self.time.format(duration as f64),
+                format!("{:.2}", betweenness[node]),
+                component[node].to_string(),
+            ]);
+        }
+
+        table
+    }
+}
+
+impl Metric for ContactNetwork {
+    fn name(&self) -> &'static str {
+        "contact-network"
+    }
+
+    fn title(&self) -> String {
+        "Connected Components of the Cumulative Contact Network".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Connected Components".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        self.detector.accumulate(frame);
+
+        for record in &frame.bots {
+            if selected(&self.bot_ids, record.bot_id) {
+                self.bots.insert(record.bot_id);
+            }
+        }
+
+        let first = self.ticks.map_or(frame.tick, |(first, _)| first);
+        self.ticks = Some((first, frame.tick));
+    }
+
+    fn finish(mut self: Box<Self>) -> Vec<Output> {
+        let contacts: Vec<(u64, u16, u16, u64)> = std::mem::take(&mut self.detector)
+            .finish()
+            .into_iter()
+            .filter_map(|encounter| match &encounter.partner {
+                Partner::Bot(other) => Some((
+                    encounter.start,
+                    encounter.bot.bot_id,
+                    other.bot_id,
+                    encounter.duration(),
+                )),
+                _ => None,
+            })
+            .filter(|(_, a, b, _)| selected(&self.bot_ids, *a) && selected(&self.bot_ids, *b))
+            .collect();
+
+        let mut graph = Graph {
+            nodes: self.bots.clone(),
+            ..Default::default()
+        };
+        for (_, a, b, duration) in &contacts {
+            graph.add_contact(*a, *b, *duration);
+        }
+
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+        plot.legend = false;
+
+        if let Some((first, last)) = self.ticks {
+            let mut components = Components::new(&graph.nodes);
+            let mut series = Series {
+                name: "Components".to_string(),
+                x: vec![self.time.value(first as f64)],
+                y: vec![components.count as f64],
+            };
+            for (start, a, b, _) in &contacts {
+                let before = components.count as f64;
+                components.join(*a, *b);
+
+                let start = self.time.value(*start as f64);
+                series.x.extend([start, start]);
+                series.y.extend([before, components.count as f64]);
+            }
+            series.x.push(self.time.value(last as f64));
+            series.y.push(components.count as f64);
+
+            plot.line(series);
+        }
+
+        vec![
+            Output::file(self.name(), "dot", graph.to_dot()),
+            Output::file(self.name(), "graphml", graph.to_graphml()),
+            Output::table(self.name(), self.table(&graph)),
+            Output::plot(self.name(), plot),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(u16, u16)]) -> Graph {
+        let mut graph = Graph::default();
+        for (a, b) in edges {
+            graph.add_contact(*a, *b, 10);
+        }
+        graph
+    }
+
+    #[test]
+    fn merges_contacts_between_the_same_bots() {
+        let graph = graph(&[(0, 1), (1, 0), (1, 2)]);
+
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(graph.edges[&(0, 1)].contacts, 2);
+        assert_eq!(graph.edges[&(0, 1)].duration, 20);
+        assert_eq!(graph.degree(), BTreeMap::from([(0, 1), (1, 2), (2, 1)]));
+    }
+
+    #[test]
+    fn betweenness_on_a_path() {
+        // On the path 0 - 1 - 2 - 3 - 4, node k lies on the k * (4 - k) shortest paths between the
+        // nodes on either side of it.
+        let graph = graph(&[(0, 1), (1, 2), (2, 3), (3, 4)]);
+
+        assert_eq!(
+            graph.betweenness(),
+            BTreeMap::from([(0, 0.0), (1, 3.0), (2, 4.0), (3, 3.0), (4, 0.0)])
+        );
+    }
+
+    #[test]
+    fn betweenness_on_a_star_and_a_cycle() {
+        let star = graph(&[(0, 1), (0, 2), (0, 3), (0, 4)]);
+        let betweenness = star.betweenness();
+        assert_eq!(betweenness[&0], 6.0);
+        assert_eq!(betweenness[&3], 0.0);
+
+        // Opposite corners of a square have two shortest paths, each through one other corner.
+        let square = graph(&[(0, 1), (1, 2), (2, 3), (3, 0)]);
+        assert!(square.betweenness().values().all(|value| *value == 0.5));
+    }
+
+    #[test]
+    fn finds_components() {
+        let mut graph = graph(&[(5, 1), (1, 3), (2, 4)]);
+        graph.nodes.insert(9);
+
+        assert_eq!(graph.components(), vec![vec![1, 3, 5], vec![2, 4], vec![9]]);
+    }
+
+    #[test]
+    fn counts_components_as_contacts_come_in() {
+        let mut components = Components::new(&BTreeSet::from([1, 2, 3, 4, 5]));
+        assert_eq!(components.count, 5);
+
+        components.join(1, 2);
+        components.join(3, 4);
+        assert_eq!(components.count, 3);
+        components.join(2, 1);
+        assert_eq!(components.count, 3);
+        components.join(4, 1);
+        assert_eq!(components.count, 2);
+        components.join(3, 2);
+        assert_eq!(components.count, 2);
+    }
+}
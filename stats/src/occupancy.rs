@@ -0,0 +1,308 @@
+//! Where the bots spend their time (or energy), binned into a grid over the arena.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::aggregate::{draw_map, plot, selected};
+use crate::map::Map;
+use crate::metric::{Metric, Options, Output};
+use crate::plot::{Heatmap, Plot};
+use crate::replay::Frame;
+use crate::time::TimeBase;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// One heatmap of the whole swarm
+    Swarm,
+    /// One heatmap per bot
+    PerBot,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weight {
+    /// Seconds spent in a cell
+    Time,
+    /// Energy used up in a cell
+    Energy,
+}
+
+// Options of the occupancy heatmap (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq)]
+pub struct OccupancyArgs {
+    /// Width and height of an occupancy and coverage cell in pixels
+    #[arg(long, default_value_t = 20.0)]
+    pub grid_size: f64,
+    #[arg(long = "occupancy-mode", value_enum, default_value_t = Mode::Swarm)]
+    pub mode: Mode,
+    #[arg(long = "occupancy-weight", value_enum, default_value_t = Weight::Time)]
+    pub weight: Weight,
+}
+
+impl Default for OccupancyArgs {
+    fn default() -> Self {
+        OccupancyArgs {
+            grid_size: 20.0,
+            mode: Mode::Swarm,
+            weight: Weight::Time,
+        }
+    }
+}
+
+pub(crate) type Cell = (i64, i64);
+
+/// The grid cell of `size` pixels the point `(x, y)` lies in.
+pub(crate) fn cell(x: f64, y: f64, size: f64) -> Cell {
+    ((x / size).floor() as i64, (y / size).floor() as i64)
+}
+
+/// Column and row ranges of the grid of cells of `size` pixels, over the whole map when there is
+/// one and over `cells` otherwise. `None` when there are no cells.
+pub(crate) fn grid_bounds<'a>(
+    cells: impl IntoIterator<Item = &'a Cell>,
+    size: f64,
+    map: Option<&Map>,
+) -> Option<((i64, i64), (i64, i64))> {
+    let ((q0, q1), (r0, r1)) = match map {
+        Some(map) => (
+            (0, (map.width / size).ceil() as i64 - 1),
+            (0, (map.height / size).ceil() as i64 - 1),
+        ),
+        None => cells.into_iter().fold(
+            ((i64::MAX, i64::MIN), (i64::MAX, i64::MIN)),
+            |((q0, q1), (r0, r1)), (q, r)| ((q0.min(*q), q1.max(*q)), (r0.min(*r), r1.max(*r))),
+        ),
+    };
+
+    (q0 <= q1 && r0 <= r1).then_some(((q0, q1), (r0, r1)))
+}
+
+/// Heatmap of the values per cell of `size` pixels, over the whole map when there is one and over
+/// the cells with values otherwise.
+pub(crate) fn grid_heatmap(
+    cells: &HashMap<Cell, f64>,
+    size: f64,
+    map: Option<&Map>,
+    label: &str,
+) -> Option<Heatmap> {
+    let ((q0, q1), (r0, r1)) = grid_bounds(cells.keys(), size, map)?;
+
+    // The log has y pointing down, the heatmap is flipped like the locations plot.
+    let top = match map {
+        Some(map) => map.height,
+        None => (r1 + 1) as f64 * size,
+    };
+
+    let columns = (q1 - q0 + 1) as usize;
+    let rows = (r1 - r0 + 1) as usize;
+    let mut values = vec![f64::NAN; columns * rows];
+    for ((q, r), value) in cells {
+        if (q0..=q1).contains(q) && (r0..=r1).contains(r) {
+            values[(r1 - r) as usize * columns + (q - q0) as usize] = *value;
+        }
+    }
+
+    Some(Heatmap {
+        x: (q0 as f64 * size, (q1 + 1) as f64 * size),
+        y: (top - (r1 + 1) as f64 * size, top - r0 as f64 * size),
+        columns,
+        rows,
+        values,
+        label: label.to_string(),
+    })
+}
+
+/// Last sample of a bot, whose cell is credited once the next sample shows how long it stayed.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    frame: usize,
+    tick: u64,
+    cell: Cell,
+    energy: f64,
+}
+
+#[derive(Default)]
+pub struct Occupancy {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    map: Option<Map>,
+    args: OccupancyArgs,
+    frames: usize,
+    last: BTreeMap<u16, Sample>,
+    /// Weight per cell, keyed by bot in per-bot mode and by `None` for the swarm.
+    grids: BTreeMap<Option<u16>, HashMap<Cell, f64>>,
+}
+
+impl Occupancy {
+    pub fn new(options: &Options) -> Self {
+        Occupancy {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            map: options.map.clone(),
+            args: options.args(),
+            ..Default::default()
+        }
+    }
+
+    fn heatmap(&self, cells: &HashMap<Cell, f64>) -> Option<Heatmap> {
+        let label = match self.args.weight {
+            Weight::Time => "Time spent (s)",
+            Weight::Energy => "Energy spent (J)",
+        };
+
+        grid_heatmap(cells, self.args.grid_size, self.map.as_ref(), label)
+    }
+
+    /// Title naming the bot in per-bot mode.
+    fn title_of(&self, bot_id: Option<u16>) -> String {
+        let subject = match bot_id {
+            Some(bot_id) => format!("Bot {}", bot_id),
+            None => "Bots".to_string(),
+        };
+
+        match self.args.weight {
+            Weight::Time => format!("Occupancy of {}", subject),
+            Weight::Energy => format!("Energy Spent by {} per Location", subject),
+        }
+    }
+
+    fn plot(&self, title: String, cells: &HashMap<Cell, f64>) -> Plot {
+        let mut plot = plot(self);
+        plot.title = title;
+
+        if let Some(heatmap) = self.heatmap(cells) {
+            plot.heatmap(heatmap);
+        }
+        if let Some(map) = &self.map {
+            draw_map(&mut plot, map);
+        }
+
+        plot
+    }
+}
+
+impl Metric for Occupancy {
+    fn name(&self) -> &'static str {
+        "occupancy"
+    }
+
+    fn title(&self) -> String {
+        self.title_of(None)
+    }
+
+    fn labels(&self) -> (String, String) {
+        ("X Coordinate".to_string(), "Y Coordinate".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            let sample = Sample {
+                frame: self.frames,
+                tick: frame.tick,
+                cell: cell(record.x, record.y, self.args.grid_size),
+                energy: record.energy,
+            };
+
+            // Bots missing from frames in between are not credited for the gap.
+            if let Some(last) = self.last.insert(record.bot_id, sample) {
+                if last.frame + 1 == self.frames {
+                    let weight = match self.args.weight {
+                        Weight::Time => self.time.seconds((frame.tick - last.tick) as f64),
+                        Weight::Energy => (last.energy - record.energy).max(0.0),
+                    };
+                    let key = match self.args.mode {
+                        Mode::Swarm => None,
+                        Mode::PerBot => Some(record.bot_id),
+                    };
+
+                    *self
+                        .grids
+                        .entry(key)
+                        .or_default()
+                        .entry(last.cell)
+                        .or_default() += weight;
+                }
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        match self.args.mode {
+            Mode::Swarm => {
+                let cells = self.grids.get(&None).cloned().unwrap_or_default();
+                vec![Output::plot(self.name(), self.plot(self.title(), &cells))]
+            }
+            Mode::PerBot => self
+                .grids
+                .iter()
+                .filter_map(|(bot_id, cells)| bot_id.map(|bot_id| (bot_id, cells)))
+                .map(|(bot_id, cells)| {
+                    let plot = self.plot(self.title_of(Some(bot_id)), cells);
+                    Output::plot(&format!("{}-bot-{}", self.name(), bot_id), plot)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(width: f64, height: f64) -> Map {
+        let object = r#"{"x": 0, "y": 0, "width": 20, "height": 20, "mass": 1}"#;
+        serde_json::from_str(&format!(
+            r#"{{"width": {}, "height": {}, "bots": [], "obstacles": [],
+                "station": {}, "target_station": {}}}"#,
+            width, height, object, object
+        ))
+        .expect("Invalid map")
+    }
+
+    #[test]
+    fn cells_floor_towards_negative_infinity() {
+        assert_eq!(cell(0.0, 0.0, 10.0), (0, 0));
+        assert_eq!(cell(9.99, 10.0, 10.0), (0, 1));
+        assert_eq!(cell(25.0, 39.0, 10.0), (2, 3));
+        assert_eq!(cell(-0.5, -10.0, 10.0), (-1, -1));
+        assert_eq!(cell(-10.5, 5.0, 10.0), (-2, 0));
+    }
+
+    #[test]
+    fn bounds_cover_the_cells_without_a_map() {
+        let cells = [(2, -1), (-3, 4), (0, 0)];
+        assert_eq!(grid_bounds(&cells, 10.0, None), Some(((-3, 2), (-1, 4))));
+        assert_eq!(grid_bounds(&[(5, 7)], 10.0, None), Some(((5, 5), (7, 7))));
+        assert_eq!(grid_bounds(&[], 10.0, None), None);
+    }
+
+    #[test]
+    fn bounds_cover_the_whole_map() {
+        let map = map(400.0, 300.0);
+        // Cells outside the map don't widen the grid, and a partial cell at the edge counts.
+        assert_eq!(
+            grid_bounds(&[(50, 50)], 100.0, Some(&map)),
+            Some(((0, 3), (0, 2)))
+        );
+        assert_eq!(grid_bounds(&[], 70.0, Some(&map)), Some(((0, 5), (0, 4))));
+    }
+
+    #[test]
+    fn heatmap_rows_are_flipped() {
+        let cells = HashMap::from([((0, 0), 1.0), ((1, 1), 2.0)]);
+        let heatmap = grid_heatmap(&cells, 10.0, None, "Time").expect("No heatmap");
+
+        assert_eq!((heatmap.columns, heatmap.rows), (2, 2));
+        assert_eq!(heatmap.x, (0.0, 20.0));
+        assert_eq!(heatmap.y, (0.0, 20.0));
+        // Row 0 of the log is at the top, so it's the last row of the heatmap.
+        assert!(heatmap.values[0].is_nan());
+        assert_eq!(heatmap.values[1], 2.0);
+        assert_eq!(heatmap.values[2], 1.0);
+        assert!(heatmap.values[3].is_nan());
+    }
+}
@@ -0,0 +1,403 @@
+//! Backend independent description of a figure.
+//!
+//! Metrics and binaries only build a [`Plot`]; a [`Backend`] turns it into a gnuplot window or file,
+//! or renders it without any external program through the [`Native`] backend.
+
+mod gnuplot;
+mod native;
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub use self::gnuplot::Gnuplot;
+pub use self::native::Native;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub const BLACK: Color = Color(0, 0, 0);
+    pub const RED: Color = Color(255, 0, 0);
+    pub const BLUE: Color = Color(0, 0, 255);
+    pub const GREEN: Color = Color(0, 128, 0);
+    pub const PURPLE: Color = Color(128, 0, 128);
+    pub const ORANGE: Color = Color(255, 165, 0);
+    pub const BROWN: Color = Color(165, 42, 42);
+    pub const CYAN: Color = Color(0, 255, 255);
+    pub const GRAY: Color = Color(128, 128, 128);
+
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Colors handed out to layers without an explicit color, gnuplot's default line colors.
+pub const PALETTE: [Color; 8] = [
+    Color(148, 0, 211),
+    Color(0, 158, 115),
+    Color(86, 180, 233),
+    Color(230, 159, 0),
+    Color(240, 228, 66),
+    Color(0, 114, 178),
+    Color(229, 30, 16),
+    Color(0, 0, 0),
+];
+
+/// Stops of the colormap used for heatmaps (viridis).
+pub const COLORMAP: [(f64, Color); 5] = [
+    (0.0, Color(68, 1, 84)),
+    (0.25, Color(59, 82, 139)),
+    (0.5, Color(33, 145, 140)),
+    (0.75, Color(94, 201, 98)),
+    (1.0, Color(253, 231, 37)),
+];
+
+/// Color of `t` (clamped to `[0, 1]`) on the [`COLORMAP`].
+pub fn colormap(t: f64) -> Color {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+    for window in COLORMAP.windows(2) {
+        let ((t0, Color(r0, g0, b0)), (t1, Color(r1, g1, b1))) = (window[0], window[1]);
+        if t <= t1 {
+            let f = (t - t0) / (t1 - t0);
+            let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * f).round() as u8;
+            return Color(mix(r0, r1), mix(g0, g1), mix(b0, b1));
+        }
+    }
+
+    COLORMAP[COLORMAP.len() - 1].1
+}
+
+/// Points of a line or scatter layer; a point with a NaN coordinate splits a line.
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub name: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bin {
+    pub start: f64,
+    pub end: f64,
+    pub value: f64,
+}
+
+/// A grid of values; `values` is row-major and the first row is the one at `y.0`.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    pub x: (f64, f64),
+    pub y: (f64, f64),
+    pub columns: usize,
+    pub rows: usize,
+    pub values: Vec<f64>,
+    /// Label of the color bar.
+    pub label: String,
+}
+
+impl Heatmap {
+    pub fn cell_size(&self) -> (f64, f64) {
+        (
+            (self.x.1 - self.x.0) / self.columns as f64,
+            (self.y.1 - self.y.0) / self.rows as f64,
+        )
+    }
+
+    /// Smallest and largest finite value.
+    pub fn value_range(&self) -> (f64, f64) {
+        self.values
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Layer {
+    Line {
+        series: Series,
+        color: Option<Color>,
+        width: f64,
+    },
+    Scatter {
+        series: Series,
+        color: Option<Color>,
+        size: f64,
+    },
+    Histogram {
+        name: String,
+        bins: Vec<Bin>,
+        color: Option<Color>,
+    },
+    Heatmap(Heatmap),
+    /// A filled, closed polygon.
+    Polygon {
+        name: String,
+        points: Vec<(f64, f64)>,
+        color: Color,
+    },
+    /// Filled rectangles between opposite corners, e.g. the segments of a timeline.
+    Rectangles {
+        name: String,
+        rectangles: Vec<[(f64, f64); 2]>,
+        color: Color,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Axis {
+    pub label: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Custom tick positions and labels, automatic ticks when empty.
+    pub ticks: Vec<(f64, String)>,
+    /// Values are seconds, automatic ticks are labelled as minutes and seconds.
+    pub clock: bool,
+}
+
+impl Axis {
+    pub fn new(label: &str) -> Self {
+        Axis {
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Font sizes in points.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub title_size: f64,
+    pub label_size: f64,
+    pub tick_size: f64,
+    pub legend_size: f64,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            title_size: 14.0,
+            label_size: 12.0,
+            tick_size: 10.0,
+            legend_size: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Plot {
+    pub title: String,
+    pub x: Axis,
+    pub y: Axis,
+    pub layers: Vec<Layer>,
+    pub legend: bool,
+    /// A unit is as long on the x axis as on the y axis, the ranges are widened to fit.
+    pub equal_axes: bool,
+    pub theme: Theme,
+}
+
+impl Plot {
+    pub fn new(title: &str, (x_label, y_label): (&str, &str)) -> Self {
+        Plot {
+            title: title.to_string(),
+            x: Axis::new(x_label),
+            y: Axis::new(y_label),
+            layers: Vec::new(),
+            legend: true,
+            equal_axes: false,
+            theme: Theme::default(),
+        }
+    }
+
+    pub fn line(&mut self, series: Series) -> &mut Self {
+        self.layers.push(Layer::Line {
+            series,
+            color: None,
+            width: 2.0,
+        });
+        self
+    }
+
+    pub fn scatter(&mut self, series: Series) -> &mut Self {
+        self.layers.push(Layer::Scatter {
+            series,
+            color: None,
+            size: 1.0,
+        });
+        self
+    }
+
+    pub fn histogram(&mut self, name: &str, bins: Vec<Bin>) -> &mut Self {
+        self.layers.push(Layer::Histogram {
+            name: name.to_string(),
+            bins,
+            color: None,
+        });
+        self
+    }
+
+    pub fn heatmap(&mut self, heatmap: Heatmap) -> &mut Self {
+        self.layers.push(Layer::Heatmap(heatmap));
+        self
+    }
+
+    pub fn polygon(&mut self, name: &str, points: Vec<(f64, f64)>, color: Color) -> &mut Self {
+        self.layers.push(Layer::Polygon {
+            name: name.to_string(),
+            points,
+            color,
+        });
+        self
+    }
+
+    pub fn rectangles(
+        &mut self,
+        name: &str,
+        rectangles: Vec<[(f64, f64); 2]>,
+        color: Color,
+    ) -> &mut Self {
+        self.layers.push(Layer::Rectangles {
+            name: name.to_string(),
+            rectangles,
+            color,
+        });
+        self
+    }
+
+    /// Explicit colors, falling back to the [`PALETTE`] in layer order.
+    pub(crate) fn layer_colors(&self) -> Vec<Color> {
+        let mut next = 0;
+        self.layers
+            .iter()
+            .map(|layer| {
+                let color = match layer {
+                    Layer::Line { color, .. }
+                    | Layer::Scatter { color, .. }
+                    | Layer::Histogram { color, .. } => *color,
+                    Layer::Heatmap(_) => return Color::BLACK,
+                    Layer::Polygon { color, .. } | Layer::Rectangles { color, .. } => {
+                        return *color
+                    }
+                };
+
+                color.unwrap_or_else(|| {
+                    next += 1;
+                    PALETTE[(next - 1) % PALETTE.len()]
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+pub trait Backend {
+    /// Opens the plot in a window.
+    fn show(&self, plot: &Plot) -> Result<(), Box<dyn Error>>;
+    fn save(&self, plot: &Plot, path: &Path, format: Format) -> Result<(), Box<dyn Error>>;
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    /// gnuplot when it is installed, the native renderer otherwise
+    Auto,
+    Gnuplot,
+    Native,
+}
+
+// Plot output options shared by the plotting binaries; no doc comment, clap would use it as the
+// about text of every binary that flattens it.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PlotArgs {
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Png)]
+    pub format: Format,
+    #[arg(long)]
+    pub no_window: bool,
+    #[arg(long, value_enum, default_value_t = BackendKind::Auto)]
+    pub backend: BackendKind,
+}
+
+impl PlotArgs {
+    pub fn renderer(&self) -> Result<Renderer, Box<dyn Error>> {
+        let gnuplot = match self.backend {
+            BackendKind::Auto => Gnuplot::available(),
+            BackendKind::Gnuplot => true,
+            BackendKind::Native => false,
+        };
+        if !gnuplot && self.format == Format::Pdf {
+            return Err("PDF output needs the gnuplot backend".into());
+        }
+
+        let mut window = !self.no_window;
+        if window && !gnuplot {
+            if self.output_dir.is_none() {
+                return Err(
+                    "windows need gnuplot, use --output-dir and --no-window to only write files"
+                        .into(),
+                );
+            }
+
+            eprintln!("gnuplot is not available, only writing files");
+            window = false;
+        }
+
+        if let Some(output_dir) = &self.output_dir {
+            fs::create_dir_all(output_dir)?;
+        }
+
+        Ok(Renderer {
+            backend: if gnuplot {
+                Box::new(Gnuplot)
+            } else {
+                Box::new(Native)
+            },
+            output_dir: self.output_dir.clone(),
+            format: self.format,
+            window,
+        })
+    }
+}
+
+pub struct Renderer {
+    backend: Box<dyn Backend>,
+    output_dir: Option<PathBuf>,
+    format: Format,
+    window: bool,
+}
+
+impl Renderer {
+    /// Writes the plot to `<output-dir>/<name>.<format>` and/or shows it, depending on the options.
+    pub fn render(&self, name: &str, plot: &Plot) -> Result<(), Box<dyn Error>> {
+        if let Some(output_dir) = &self.output_dir {
+            let path = output_dir.join(format!("{}.{}", name, self.format.extension()));
+            self.backend.save(plot, &path, self.format)?;
+            println!("Saved {}", path.display());
+        }
+
+        if self.window {
+            self.backend.show(plot)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use std::error::Error;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use ::gnuplot::{
+    AutoOption, AxesCommon, BorderColor, Caption, Color as LineColor, Coordinate, Figure,
+    FillAlpha, LabelOption, LineWidth, PaletteType, PointSize, PointSymbol, Tick, TickOption,
+};
+
+use super::{Axis, Backend, Format, Layer, Plot, COLORMAP};
+
+/// Renders through the `gnuplot` executable.
+pub struct Gnuplot;
+
+impl Gnuplot {
+    pub fn available() -> bool {
+        Command::new("gnuplot")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+}
+
+impl Backend for Gnuplot {
+    fn show(&self, plot: &Plot) -> Result<(), Box<dyn Error>> {
+        figure(plot).show()?;
+        Ok(())
+    }
+
+    fn save(&self, plot: &Plot, path: &Path, format: Format) -> Result<(), Box<dyn Error>> {
+        let mut fg = figure(plot);
+
+        match format {
+            Format::Png => fg.save_to_png(path, 1600, 900)?,
+            Format::Svg => fg.save_to_svg(path, 1600, 900)?,
+            Format::Pdf => fg.save_to_pdf(path, 16.0, 9.0)?,
+        }
+
+        Ok(())
+    }
+}
+
+fn range(value: Option<f64>) -> AutoOption<f64> {
+    value.map_or(AutoOption::Auto, AutoOption::Fix)
+}
+
+/// Labels seconds as minutes (not wrapped at the hour) and seconds.
+fn clock_format(axis: &Axis) -> Vec<TickOption<&'static str>> {
+    if axis.clock {
+        vec![TickOption::Format("%tM:%02.0tS")]
+    } else {
+        Vec::new()
+    }
+}
+
+fn figure(plot: &Plot) -> Figure {
+    let theme = plot.theme;
+    let mut fg = Figure::new();
+
+    let mut axes = fg
+        .axes2d()
+        .set_title(&plot.title, &[LabelOption::Font("", theme.title_size)])
+        .set_x_label(&plot.x.label, &[LabelOption::Font("", theme.label_size)])
+        .set_y_label(&plot.y.label, &[LabelOption::Font("", theme.label_size)])
+        .set_x_range(range(plot.x.min), range(plot.x.max))
+        .set_y_range(range(plot.y.min), range(plot.y.max));
+
+    let tick_font = [LabelOption::Font("", theme.tick_size)];
+    axes = if plot.x.ticks.is_empty() {
+        axes.set_x_time(plot.x.clock).set_x_ticks(
+            Some((AutoOption::Auto, 0)),
+            &clock_format(&plot.x),
+            &tick_font,
+        )
+    } else {
+        axes.set_x_ticks_custom(
+            plot.x
+                .ticks
+                .iter()
+                .map(|(at, label)| Tick::Major(*at, AutoOption::Fix(label.clone()))),
+            &[],
+            &tick_font,
+        )
+    };
+    axes = if plot.y.ticks.is_empty() {
+        axes.set_y_time(plot.y.clock).set_y_ticks(
+            Some((AutoOption::Auto, 0)),
+            &clock_format(&plot.y),
+            &tick_font,
+        )
+    } else {
+        axes.set_y_ticks_custom(
+            plot.y
+                .ticks
+                .iter()
+                .map(|(at, label)| Tick::Major(*at, AutoOption::Fix(label.clone()))),
+            &[],
+            &tick_font,
+        )
+    };
+
+    if plot.equal_axes {
+        axes = axes.set_aspect_ratio(AutoOption::Fix(-1.0));
+    }
+
+    if plot.legend {
+        axes = axes.set_legend(
+            Coordinate::Graph(0.98),
+            Coordinate::Graph(0.98),
+            &[],
+            &[LabelOption::Font("", theme.legend_size)],
+        );
+    }
+
+    for (layer, color) in plot.layers.iter().zip(plot.layer_colors()) {
+        let color = color.hex();
+        let caption = |name: &str| {
+            if plot.legend {
+                name.to_string()
+            } else {
+                String::new()
+            }
+        };
+
+        match layer {
+            Layer::Line { series, width, .. } => {
+                axes = axes.lines(
+                    &series.x,
+                    &series.y,
+                    &[
+                        Caption(&caption(&series.name)),
+                        LineWidth(*width),
+                        LineColor(&color),
+                    ],
+                );
+            }
+            Layer::Scatter { series, size, .. } => {
+                axes = axes.points(
+                    &series.x,
+                    &series.y,
+                    &[
+                        Caption(&caption(&series.name)),
+                        PointSymbol('O'),
+                        PointSize(*size),
+                        LineColor(&color),
+                    ],
+                );
+            }
+            Layer::Histogram { name, bins, .. } => {
+                axes = axes.boxes_set_width(
+                    bins.iter().map(|bin| (bin.start + bin.end) / 2.0),
+                    bins.iter().map(|bin| bin.value),
+                    bins.iter().map(|bin| bin.end - bin.start),
+                    &[Caption(&caption(name)), LineColor(&color), FillAlpha(0.6)],
+                );
+            }
+            Layer::Polygon { name, points, .. } => {
+                axes = axes.polygon(
+                    points.iter().map(|(x, _)| *x),
+                    points.iter().map(|(_, y)| *y),
+                    &[Caption(&caption(name)), LineColor(&color), FillAlpha(0.5)],
+                );
+            }
+            Layer::Rectangles {
+                name, rectangles, ..
+            } => {
+                let x = |i: usize| rectangles.iter().map(move |corners| corners[i].0);
+                let y = |i: usize| rectangles.iter().map(move |corners| corners[i].1);
+                axes = axes.box_xy_error_low_high(
+                    x(0),
+                    y(0),
+                    x(0),
+                    x(1),
+                    y(0),
+                    y(1),
+                    &[
+                        Caption(&caption(name)),
+                        LineColor(&color),
+                        BorderColor(&color),
+                        FillAlpha(0.8),
+                    ],
+                );
+            }
+            Layer::Heatmap(heatmap) => {
+                let (width, height) = heatmap.cell_size();
+                let palette: Vec<(f32, f32, f32, f32)> = COLORMAP
+                    .iter()
+                    .map(|(t, c)| {
+                        (
+                            *t as f32,
+                            c.0 as f32 / 255.0,
+                            c.1 as f32 / 255.0,
+                            c.2 as f32 / 255.0,
+                        )
+                    })
+                    .collect();
+
+                axes = axes
+                    .set_palette(PaletteType::Custom(&palette))
+                    .set_cb_label(&heatmap.label, &[LabelOption::Font("", theme.label_size)])
+                    .image(
+                        &heatmap.values,
+                        heatmap.rows,
+                        heatmap.columns,
+                        Some((
+                            heatmap.x.0 + width / 2.0,
+                            heatmap.y.0 + height / 2.0,
+                            heatmap.x.1 - width / 2.0,
+                            heatmap.y.1 - height / 2.0,
+                        )),
+                        &[],
+                    );
+            }
+        }
+    }
+
+    fg
+}
@@ -0,0 +1,573 @@
+use std::error::Error;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Once;
+
+use plotters::coord::ranged1d::{DefaultFormatting, KeyPointHint};
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::{register_font, Color as _, FontStyle};
+
+use crate::time;
+
+use super::{colormap, Axis, Backend, Color, Format, Heatmap, Layer, Plot, Series, Theme};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+/// Pixels per point of the theme's font sizes.
+const FONT_SCALE: f64 = 1.5;
+
+const FONT: &[u8] = include_bytes!("../../../Roboto-Regular.ttf");
+static REGISTER_FONT: Once = Once::new();
+
+/// Pure-Rust renderer for PNG and SVG files, works without any external program.
+pub struct Native;
+
+impl Backend for Native {
+    fn show(&self, _plot: &Plot) -> Result<(), Box<dyn Error>> {
+        Err("the native backend can't open windows, write the plot with --output-dir".into())
+    }
+
+    fn save(&self, plot: &Plot, path: &Path, format: Format) -> Result<(), Box<dyn Error>> {
+        REGISTER_FONT.call_once(|| {
+            register_font("sans-serif", FontStyle::Normal, FONT)
+                .unwrap_or_else(|_| panic!("Couldn't load the bundled font"));
+        });
+
+        match format {
+            Format::Png => draw(
+                BitMapBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area(),
+                plot,
+            ),
+            Format::Svg => draw(
+                SVGBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area(),
+                plot,
+            ),
+            Format::Pdf => Err("PDF output needs the gnuplot backend".into()),
+        }
+    }
+}
+
+fn rgb(color: Color) -> RGBColor {
+    RGBColor(color.0, color.1, color.2)
+}
+
+fn font(size: f64) -> (&'static str, f64) {
+    ("sans-serif", size * FONT_SCALE)
+}
+
+/// Data bounds of all layers as `(x, y)` ranges.
+fn bounds(plot: &Plot) -> (Range<f64>, Range<f64>) {
+    let mut x = (f64::INFINITY, f64::NEG_INFINITY);
+    let mut y = (f64::INFINITY, f64::NEG_INFINITY);
+    let mut extend = |(px, py): (f64, f64)| {
+        if px.is_finite() && py.is_finite() {
+            x = (x.0.min(px), x.1.max(px));
+            y = (y.0.min(py), y.1.max(py));
+        }
+    };
+
+    for layer in &plot.layers {
+        match layer {
+            Layer::Line { series, .. } | Layer::Scatter { series, .. } => {
+                series
+                    .x
+                    .iter()
+                    .zip(&series.y)
+                    .for_each(|(px, py)| extend((*px, *py)));
+            }
+            Layer::Histogram { bins, .. } => {
+                for bin in bins {
+                    extend((bin.start, 0.0));
+                    extend((bin.end, bin.value));
+                }
+            }
+            Layer::Heatmap(heatmap) => {
+                extend((heatmap.x.0, heatmap.y.0));
+                extend((heatmap.x.1, heatmap.y.1));
+            }
+            Layer::Polygon { points, .. } => points.iter().for_each(|point| extend(*point)),
+            Layer::Rectangles { rectangles, .. } => rectangles
+                .iter()
+                .flatten()
+                .for_each(|corner| extend(*corner)),
+        }
+    }
+
+    (x.0..x.1, y.0..y.1)
+}
+
+/// Widens the shorter of the ranges so a unit is as long on both axes of a plot area of `size`
+/// pixels.
+fn equalize(x: &mut Range<f64>, y: &mut Range<f64>, (width, height): (f64, f64)) {
+    let scale = ((x.end - x.start) / width).max((y.end - y.start) / height);
+    let widen = |range: &mut Range<f64>, pixels: f64| {
+        let pad = (scale * pixels - (range.end - range.start)) / 2.0;
+        *range = range.start - pad..range.end + pad;
+    };
+
+    widen(x, width);
+    widen(y, height);
+}
+
+/// Steps in seconds between ticks of a clock axis.
+const CLOCK_STEPS: [f64; 10] = [1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Step between "nice" ticks (1, 2 or 5 times a power of ten, or whole seconds and minutes on a
+/// clock axis) for about eight ticks.
+fn tick_step(axis: &Axis, span: f64) -> f64 {
+    let raw = span / 8.0;
+    if axis.clock && raw >= 1.0 {
+        return CLOCK_STEPS
+            .into_iter()
+            .find(|step| *step >= raw)
+            .unwrap_or_else(|| (raw / 600.0).ceil() * 600.0);
+    }
+
+    let magnitude = 10f64.powf(raw.log10().floor());
+
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Axis range and tick positions; automatic ends cover custom ticks and are widened to the next
+/// tick like gnuplot does.
+fn axis_range(axis: &Axis, data: Range<f64>) -> (Range<f64>, Vec<f64>) {
+    let (mut min, mut max) = if data.start <= data.end {
+        (data.start, data.end)
+    } else {
+        (0.0, 1.0)
+    };
+    for (at, _) in &axis.ticks {
+        min = min.min(*at);
+        max = max.max(*at);
+    }
+    min = axis.min.unwrap_or(min);
+    max = axis.max.unwrap_or(max);
+    if max <= min {
+        max = min + 1.0;
+    }
+
+    let step = tick_step(axis, max - min);
+    if axis.min.is_none() {
+        min = (min / step).floor() * step;
+    }
+    if axis.max.is_none() {
+        max = (max / step).ceil() * step;
+    }
+
+    let ticks = if axis.ticks.is_empty() {
+        let first = (min / step).ceil() as i64;
+        let last = (max / step).floor() as i64;
+        (first..=last).map(|i| i as f64 * step).collect()
+    } else {
+        axis.ticks.iter().map(|(at, _)| *at).collect()
+    };
+
+    (min..max, ticks)
+}
+
+fn tick_label(axis: &Axis, value: f64, step: f64) -> String {
+    if let Some((_, label)) = axis
+        .ticks
+        .iter()
+        .find(|(at, _)| (at - value).abs() <= f64::EPSILON * at.abs().max(1.0))
+    {
+        return label.clone();
+    }
+    if axis.clock && step >= 1.0 {
+        return time::clock(value);
+    }
+
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+/// Runs of finite points, a line is split wherever a coordinate is not finite.
+fn segments(series: &Series) -> Vec<Vec<(f64, f64)>> {
+    let mut segments = vec![Vec::new()];
+    for (x, y) in series.x.iter().zip(&series.y) {
+        if x.is_finite() && y.is_finite() {
+            segments.last_mut().expect("No segment").push((*x, *y));
+        } else if !segments.last().expect("No segment").is_empty() {
+            segments.push(Vec::new());
+        }
+    }
+
+    segments
+}
+
+/// A float axis with fixed tick positions.
+struct Ticked {
+    inner: RangedCoordf64,
+    ticks: Vec<f64>,
+}
+
+impl Ranged for Ticked {
+    type ValueType = f64;
+    type FormatOption = DefaultFormatting;
+
+    fn range(&self) -> Range<f64> {
+        self.inner.range()
+    }
+
+    fn map(&self, value: &f64, limit: (i32, i32)) -> i32 {
+        self.inner.map(value, limit)
+    }
+
+    fn key_points<Hint: KeyPointHint>(&self, _hint: Hint) -> Vec<f64> {
+        self.ticks.clone()
+    }
+}
+
+/// Draws the color bar of a heatmap, lined up with the top and bottom of the plot area.
+fn colorbar<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    heatmap: &Heatmap,
+    theme: &Theme,
+    (top, bottom): (f64, f64),
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let (min, max) = heatmap.value_range();
+    if !(min.is_finite() && max.is_finite()) {
+        return Ok(());
+    }
+    let max = if max > min { max } else { min + 1.0 };
+
+    let mut chart = ChartBuilder::on(area)
+        .margin_top(top)
+        .margin_bottom(bottom)
+        .margin_right(20)
+        .set_label_area_size(
+            LabelAreaPosition::Right,
+            (theme.label_size * 1.5 + theme.tick_size * 4.0) * FONT_SCALE,
+        )
+        .build_cartesian_2d(0.0..1.0, min..max)?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .disable_x_axis()
+        .y_desc(heatmap.label.as_str())
+        .axis_desc_style(font(theme.label_size))
+        .label_style(font(theme.tick_size))
+        .draw()?;
+
+    const STEPS: usize = 100;
+    chart.draw_series((0..STEPS).map(|i| {
+        let (from, to) = (i as f64 / STEPS as f64, (i + 1) as f64 / STEPS as f64);
+        let fill = rgb(colormap(from)).filled();
+        Rectangle::new(
+            [
+                (0.0, min + from * (max - min)),
+                (1.0, min + to * (max - min)),
+            ],
+            fill,
+        )
+    }))?;
+
+    Ok(())
+}
+
+fn draw<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, plot: &Plot) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let theme = plot.theme;
+    root.fill(&WHITE)?;
+
+    let (x_data, y_data) = bounds(plot);
+    let (mut x_range, x_ticks) = axis_range(&plot.x, x_data);
+    let (mut y_range, y_ticks) = axis_range(&plot.y, y_data);
+    let x_step = tick_step(&plot.x, x_range.end - x_range.start);
+    let y_step = tick_step(&plot.y, y_range.end - y_range.start);
+
+    // Room for the axis label and the widest tick label, assuming glyphs of about 0.6em.
+    let widest = y_ticks
+        .iter()
+        .map(|at| tick_label(&plot.y, *at, y_step).chars().count())
+        .max()
+        .unwrap_or(0);
+    let x_area = (theme.label_size * 1.5 + theme.tick_size * 2.0) * FONT_SCALE;
+    let y_area =
+        (theme.label_size * 1.5 + theme.tick_size * (0.6 * widest as f64 + 1.0)) * FONT_SCALE;
+
+    let heatmap = plot.layers.iter().find_map(|layer| match layer {
+        Layer::Heatmap(heatmap) => Some(heatmap),
+        _ => None,
+    });
+    let bar_width = (theme.label_size * 1.5 + theme.tick_size * 6.0) * FONT_SCALE + 60.0;
+    let title_height = if plot.title.is_empty() {
+        0.0
+    } else {
+        theme.title_size * FONT_SCALE * 1.5
+    };
+
+    if plot.equal_axes {
+        let (width, height) = root.dim_in_pixel();
+        let bar = if heatmap.is_some() { bar_width } else { 0.0 };
+        let size = (
+            width as f64 - bar - y_area - 40.0,
+            height as f64 - title_height - x_area - 40.0,
+        );
+        equalize(&mut x_range, &mut y_range, size);
+    }
+
+    let x_coord = Ticked {
+        inner: x_range.into(),
+        ticks: x_ticks,
+    };
+    let y_coord = Ticked {
+        inner: y_range.into(),
+        ticks: y_ticks,
+    };
+
+    let (area, bar) = match heatmap {
+        Some(heatmap) => {
+            let (area, bar) =
+                root.split_horizontally(root.dim_in_pixel().0 as i32 - bar_width as i32);
+            (area, Some((bar, heatmap)))
+        }
+        None => (root.clone(), None),
+    };
+
+    let mut builder = ChartBuilder::on(&area);
+    if !plot.title.is_empty() {
+        builder.caption(&plot.title, font(theme.title_size));
+    }
+
+    let mut chart = builder
+        .margin(20)
+        .x_label_area_size(x_area)
+        .y_label_area_size(y_area)
+        .build_cartesian_2d(x_coord, y_coord)?;
+
+    let x_formatter = |v: &f64| tick_label(&plot.x, *v, x_step);
+    let y_formatter = |v: &f64| tick_label(&plot.y, *v, y_step);
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc(plot.x.label.as_str())
+        .y_desc(plot.y.label.as_str())
+        .axis_desc_style(font(theme.label_size))
+        .label_style(font(theme.tick_size))
+        .x_label_formatter(&x_formatter)
+        .y_label_formatter(&y_formatter)
+        .draw()?;
+
+    let mut labelled = false;
+    for (layer, color) in plot.layers.iter().zip(plot.layer_colors()) {
+        let color = rgb(color);
+
+        match layer {
+            Layer::Line { series, width, .. } => {
+                let style = color.stroke_width(*width as u32);
+                let drawn = chart.draw_series(
+                    segments(series)
+                        .into_iter()
+                        .map(|segment| PathElement::new(segment, style)),
+                )?;
+
+                if plot.legend && !series.name.is_empty() {
+                    labelled = true;
+                    drawn
+                        .label(series.name.as_str())
+                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], style));
+                }
+            }
+            Layer::Scatter { series, size, .. } => {
+                let radius = (*size * 3.0).round() as i32;
+                let style = color.filled();
+                let drawn = chart.draw_series(
+                    series
+                        .x
+                        .iter()
+                        .zip(&series.y)
+                        .filter(|(x, y)| x.is_finite() && y.is_finite())
+                        .map(|(x, y)| Circle::new((*x, *y), radius, style)),
+                )?;
+
+                if plot.legend && !series.name.is_empty() {
+                    labelled = true;
+                    drawn
+                        .label(series.name.as_str())
+                        .legend(move |(x, y)| Circle::new((x + 10, y), radius, style));
+                }
+            }
+            Layer::Histogram { name, bins, .. } => {
+                let style = color.mix(0.6).filled();
+                let drawn = chart
+                    .draw_series(bins.iter().map(|bin| {
+                        Rectangle::new([(bin.start, 0.0), (bin.end, bin.value)], style)
+                    }))?;
+
+                if plot.legend && !name.is_empty() {
+                    labelled = true;
+                    drawn
+                        .label(name.as_str())
+                        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], style));
+                }
+            }
+            Layer::Heatmap(heatmap) => {
+                let (width, height) = heatmap.cell_size();
+                let (min, max) = heatmap.value_range();
+                let span = if max > min { max - min } else { 1.0 };
+
+                chart.draw_series(heatmap.values.iter().enumerate().filter_map(|(i, v)| {
+                    if !v.is_finite() {
+                        return None;
+                    }
+
+                    let x = heatmap.x.0 + (i % heatmap.columns) as f64 * width;
+                    let y = heatmap.y.0 + (i / heatmap.columns) as f64 * height;
+                    let fill = rgb(colormap((v - min) / span)).filled();
+                    Some(Rectangle::new([(x, y), (x + width, y + height)], fill))
+                }))?;
+            }
+            Layer::Polygon { name, points, .. } => {
+                let fill = color.mix(0.5).filled();
+                let mut outline = points.clone();
+                outline.extend(points.first());
+
+                chart.draw_series(std::iter::once(Polygon::new(points.clone(), fill)))?;
+                let drawn = chart.draw_series(std::iter::once(PathElement::new(outline, color)))?;
+
+                if plot.legend && !name.is_empty() {
+                    labelled = true;
+                    drawn
+                        .label(name.as_str())
+                        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], fill));
+                }
+            }
+            Layer::Rectangles {
+                name, rectangles, ..
+            } => {
+                let fill = color.mix(0.8).filled();
+                let drawn = chart.draw_series(
+                    rectangles
+                        .iter()
+                        .map(|corners| Rectangle::new(*corners, fill)),
+                )?;
+
+                if plot.legend && !name.is_empty() {
+                    labelled = true;
+                    drawn
+                        .label(name.as_str())
+                        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], fill));
+                }
+            }
+        }
+    }
+
+    if let Some((bar, heatmap)) = bar {
+        colorbar(&bar, heatmap, &theme, (title_height + 20.0, x_area + 20.0))?;
+    }
+
+    if labelled {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperRight)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .label_font(font(theme.legend_size))
+            .draw()?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn clock() -> Axis {
+        Axis {
+            clock: true,
+            ..Axis::new("Time")
+        }
+    }
+
+    #[test]
+    fn picks_nice_tick_steps() {
+        let plain = Axis::new("x");
+        assert_eq!(tick_step(&plain, 100.0), 20.0);
+        assert_eq!(tick_step(&plain, 8.0), 1.0);
+        assert_eq!(tick_step(&plain, 30.0), 5.0);
+        assert!((tick_step(&plain, 0.8) - 0.1).abs() < 1e-12);
+
+        assert_eq!(tick_step(&clock(), 100.0), 15.0);
+        assert_eq!(tick_step(&clock(), 400.0), 60.0);
+        assert_eq!(tick_step(&clock(), 10000.0), 1800.0);
+        // Below a second a clock axis falls back to decimal steps.
+        assert!((tick_step(&clock(), 4.0) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn widens_automatic_ends_to_the_next_tick() {
+        let (range, ticks) = axis_range(&Axis::new("x"), 0.3..9.7);
+        assert_eq!(range, 0.0..10.0);
+        assert_close(&ticks, &[0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+    }
+
+    #[test]
+    fn keeps_fixed_ends() {
+        let axis = Axis {
+            min: Some(1.0),
+            ..Axis::new("x")
+        };
+        let (range, ticks) = axis_range(&axis, 3.0..9.7);
+        assert_eq!(range, 1.0..10.0);
+        assert_close(&ticks, &[2.0, 4.0, 6.0, 8.0, 10.0]);
+    }
+
+    #[test]
+    fn covers_custom_ticks() {
+        let axis = Axis {
+            ticks: vec![(0.0, "a".to_string()), (50.0, "b".to_string())],
+            ..Axis::new("x")
+        };
+        let (range, ticks) = axis_range(&axis, 10.0..20.0);
+        assert_eq!(range, 0.0..50.0);
+        assert_close(&ticks, &[0.0, 50.0]);
+    }
+
+    #[test]
+    fn falls_back_to_unit_range_without_data() {
+        let (range, ticks) = axis_range(&Axis::new("x"), f64::INFINITY..f64::NEG_INFINITY);
+        assert_eq!(range, 0.0..1.0);
+        assert_close(&ticks, &[0.0, 0.2, 0.4, 0.6, 0.8, 1.0]);
+    }
+
+    #[test]
+    fn splits_lines_at_non_finite_points() {
+        let series = Series {
+            name: String::new(),
+            x: vec![f64::NAN, 1.0, 2.0, f64::NAN, f64::INFINITY, 3.0, 4.0],
+            y: vec![0.0, 1.0, 2.0, 0.0, 0.0, f64::NAN, 4.0],
+        };
+        assert_eq!(
+            segments(&series),
+            vec![vec![(1.0, 1.0), (2.0, 2.0)], vec![(4.0, 4.0)]]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! How data spreads through the swarm: when every bot, the station and the target station first
+//! held each data value.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::aggregate::{plot, selected};
+use crate::metric::{Metric, Options, Output};
+use crate::plot::Series;
+use crate::replay::{Frame, Record};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+/// Something that can hold data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Holder {
+    Station,
+    TargetStation,
+    Bot(u16),
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Holder::Station => write!(f, "Station"),
+            Holder::TargetStation => write!(f, "Target Station"),
+            Holder::Bot(bot_id) => write!(f, "Bot {}", bot_id),
+        }
+    }
+}
+
+/// Median of sorted values.
+pub(crate) fn median(sorted: &[u64]) -> Option<f64> {
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(sorted[n / 2] as f64),
+        n => Some((sorted[n / 2 - 1] + sorted[n / 2]) as f64 / 2.0),
+    }
+}
+
+#[derive(Default)]
+pub struct DataPropagation {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    bots: BTreeSet<u16>,
+    last_tick: u64,
+    /// First tick each holder had each data value.
+    acquired: BTreeMap<u8, BTreeMap<Holder, u64>>,
+}
+
+impl DataPropagation {
+    pub fn new(options: &Options) -> Self {
+        DataPropagation {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+
+    fn acquire(&mut self, holder: Holder, record: &Record) {
+        for value in &record.data {
+            self.acquired
+                .entry(*value)
+                .or_default()
+                .entry(holder)
+                .or_insert(record.tick);
+        }
+    }
+
+    /// First tick each data value was held by anything, and the ticks it took to reach each bot.
+    fn spread(&self) -> BTreeMap<u8, (u64, Vec<u64>)> {
+        self.acquired
+            .iter()
+            .map(|(value, holders)| {
+                let origin = *holders.values().min().expect("Data value without holder");
+                let mut delays: Vec<u64> = holders
+                    .iter()
+                    .filter(|(holder, _)| matches!(holder, Holder::Bot(_)))
+                    .map(|(_, tick)| tick - origin)
+                    .collect();
+                delays.sort();
+
+                (*value, (origin, delays))
+            })
+            .collect()
+    }
+
+    fn summary(&self) -> Table {
+        let unit = self.time.unit.name();
+        let origin = format!("First Seen ({})", unit);
+        let target = format!("Reached Target ({})", unit);
+        let median_spread = format!("Median Spread ({})", unit);
+        let max_spread = format!("Max Spread ({})", unit);
+        let mut table = Table::new(
+            "Data Propagation",
+            &[
+                "Data",
+                &origin,
+                "Bots Reached",
+                &median_spread,
+                &max_spread,
+                &target,
+            ],
+        );
+
+        for (value, (origin, delays)) in self.spread() {
+            let time = |ticks: Option<f64>| ticks.map_or("-".to_string(), |t| self.time.format(t));
+            let target = self.acquired[&value].get(&Holder::TargetStation);
+
+            table.push(vec![
+                value.to_string(),
+                self.time.format(origin as f64),
+                format!("{}/{}", delays.len(), self.bots.len()),
+                time(median(&delays)),
+                time(delays.last().map(|delay| *delay as f64)),
+                time(target.map(|tick| *tick as f64)),
+            ]);
+        }
+
+        table
+    }
+
+    fn acquisitions(&self) -> Table {
+        let acquired = format!("First Acquired ({})", self.time.unit.name());
+        let mut table = Table::new("First Acquisition of Data", &["Data", "Holder", &acquired]);
+
+        for (value, holders) in &self.acquired {
+            let mut holders: Vec<(&Holder, &u64)> = holders.iter().collect();
+            holders.sort_by_key(|(holder, tick)| (**tick, **holder));
+
+            for (holder, tick) in holders {
+                table.push(vec![
+                    value.to_string(),
+                    holder.to_string(),
+                    self.time.format(*tick as f64),
+                ]);
+            }
+        }
+
+        table
+    }
+}
+
+impl Metric for DataPropagation {
+    fn name(&self) -> &'static str {
+        "data-propagation"
+    }
+
+    fn title(&self) -> String {
+        "Data Propagation Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Bots Reached".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        for record in &frame.bots {
+            if selected(&self.bot_ids, record.bot_id) {
+                self.bots.insert(record.bot_id);
+                self.acquire(Holder::Bot(record.bot_id), record);
+            }
+        }
+        if let Some(station) = &frame.station {
+            self.acquire(Holder::Station, station);
+        }
+        if let Some(target_station) = &frame.target_station {
+            self.acquire(Holder::TargetStation, target_station);
+        }
+
+        self.last_tick = frame.tick;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+
+        // "Infection" curve of each data value, stepping up as bots first get it.
+        for (value, (origin, delays)) in self.spread() {
+            let mut series = Series {
+                name: format!("Data {}", value),
+                x: vec![self.time.value(origin as f64)],
+                y: vec![0.0],
+            };
+            for (count, delay) in delays.iter().enumerate() {
+                let tick = self.time.value((origin + delay) as f64);
+                series.x.extend([tick, tick]);
+                series.y.extend([count as f64, count as f64 + 1.0]);
+            }
+            series.x.push(self.time.value(self.last_tick as f64));
+            series.y.push(delays.len() as f64);
+
+            plot.line(series);
+        }
+
+        vec![
+            Output::table(self.name(), self.summary()),
+            Output::table(
+                &format!("{}-acquisitions", self.name()),
+                self.acquisitions(),
+            ),
+            Output::plot(self.name(), plot),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{BotStatus, ObjectType};
+
+    fn record(bot_id: u16, tick: u64, data: &[u8], r#type: ObjectType) -> Record {
+        Record {
+            tick,
+            bot_id,
+            energy: 100.0,
+            data: data.to_vec(),
+            x: 0.0,
+            y: 0.0,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            rotation: 0.0,
+            status: BotStatus::Active,
+            color: [0.0; 4],
+            r#type,
+        }
+    }
+
+    /// A tick with the data each bot, the station and the target station hold.
+    type Holdings<'a> = (u64, &'a [&'a [u8]], &'a [u8], &'a [u8]);
+
+    fn propagation(frames: &[Holdings]) -> DataPropagation {
+        let mut propagation = DataPropagation::default();
+        for (tick, bots, station, target_station) in frames {
+            propagation.accumulate(&Frame {
+                tick: *tick,
+                bots: bots
+                    .iter()
+                    .enumerate()
+                    .map(|(bot_id, data)| record(bot_id as u16, *tick, data, ObjectType::Bot))
+                    .collect(),
+                station: Some(record(0, *tick, station, ObjectType::Station)),
+                target_station: Some(record(0, *tick, target_station, ObjectType::TargetStation)),
+            });
+        }
+        propagation
+    }
+
+    #[test]
+    fn takes_the_median_of_sorted_values() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[7]), Some(7.0));
+        assert_eq!(median(&[1, 2, 9]), Some(2.0));
+        assert_eq!(median(&[2, 4, 6, 10]), Some(5.0));
+    }
+
+    #[test]
+    fn keeps_the_first_acquisition() {
+        // Bot 0 hands data 1 over, loses it and gets it back; the station keeps data 2.
+        let propagation = propagation(&[
+            (10, &[&[], &[]], &[2], &[]),
+            (12, &[&[1], &[]], &[2], &[]),
+            (14, &[&[], &[1]], &[2], &[]),
+            (16, &[&[1, 2], &[1]], &[2], &[1]),
+        ]);
+
+        assert_eq!(
+            propagation.acquired[&1],
+            BTreeMap::from([
+                (Holder::Bot(0), 12),
+                (Holder::Bot(1), 14),
+                (Holder::TargetStation, 16),
+            ])
+        );
+        assert_eq!(
+            propagation.acquired[&2],
+            BTreeMap::from([(Holder::Station, 10), (Holder::Bot(0), 16)])
+        );
+    }
+
+    #[test]
+    fn spreads_from_the_first_holder_to_every_bot() {
+        // Data 3 starts at the station and reaches the bots 4, 10 and 20 ticks later.
+        let propagation = propagation(&[
+            (10, &[&[], &[], &[]], &[3], &[]),
+            (14, &[&[], &[3], &[]], &[3], &[]),
+            (20, &[&[3], &[3], &[]], &[3], &[]),
+            (30, &[&[3], &[3], &[3]], &[3], &[3]),
+            (32, &[&[], &[], &[]], &[3], &[3]),
+        ]);
+
+        assert_eq!(propagation.spread()[&3], (10, vec![4, 10, 20]));
+        assert_eq!(
+            propagation.summary().rows,
+            vec![["3", "10", "3/3", "10", "20", "30"].map(String::from)]
+        );
+    }
+
+    #[test]
+    fn leaves_out_holders_that_never_got_the_data() {
+        let propagation = propagation(&[
+            (0, &[&[4], &[]], &[], &[]),
+            (2, &[&[4], &[], &[4]], &[], &[]),
+        ]);
+
+        assert_eq!(propagation.spread()[&4], (0, vec![0, 2]));
+        assert_eq!(
+            propagation.summary().rows,
+            vec![["4", "0", "2/3", "1", "2", "-"].map(String::from)]
+        );
+    }
+}
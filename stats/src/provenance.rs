@@ -0,0 +1,361 @@
+//! Where every holder got each data value from, and the relay chains that carried data to the
+//! target station.
+//!
+//! A bot that has a new data value after an [encounter](crate::encounter) got it from its partner.
+//! A station that gets a new value got it from the last bot that was in contact with it holding the
+//! value. Values held from a holder's first sample on have no source, they start a chain. A chain
+//! that can't be traced back to such a holder starts with `Unknown`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::aggregate::selected;
+use crate::encounter::{Detector, Encounter, Partner};
+use crate::metric::{Metric, Options, Output};
+use crate::propagation::Holder;
+use crate::replay::{Frame, Record};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+#[derive(Default)]
+pub struct DataProvenance {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    detector: Detector,
+    /// Data of each holder at its first sample.
+    initial: BTreeMap<Holder, Vec<u8>>,
+    /// First tick each station held each data value.
+    stations: BTreeMap<(Holder, u8), u64>,
+}
+
+impl DataProvenance {
+    pub fn new(options: &Options) -> Self {
+        DataProvenance {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            detector: Detector::new(options.args(), options.map.as_ref()),
+            ..Default::default()
+        }
+    }
+
+    fn sample(&mut self, holder: Holder, record: &Record) {
+        self.initial
+            .entry(holder)
+            .or_insert_with(|| record.data.clone());
+
+        if !matches!(holder, Holder::Bot(_)) {
+            for value in &record.data {
+                self.stations.entry((holder, *value)).or_insert(record.tick);
+            }
+        }
+    }
+
+    /// Source and tick of every data value a holder received after its first sample.
+    fn sources(&self, encounters: &[Encounter]) -> BTreeMap<(Holder, u8), (Holder, u64)> {
+        let mut sources = BTreeMap::new();
+
+        for encounter in encounters {
+            let bot = Holder::Bot(encounter.bot.bot_id);
+            let partner = match &encounter.partner {
+                Partner::Bot(other) => {
+                    for value in other.received() {
+                        sources
+                            .entry((Holder::Bot(other.bot_id), value))
+                            .or_insert((bot, encounter.end));
+                    }
+                    Holder::Bot(other.bot_id)
+                }
+                Partner::Station => Holder::Station,
+                Partner::TargetStation => Holder::TargetStation,
+                Partner::Unknown => continue,
+            };
+
+            for value in encounter.bot.received() {
+                sources
+                    .entry((bot, value))
+                    .or_insert((partner, encounter.end));
+            }
+        }
+
+        for ((station, value), tick) in &self.stations {
+            if self.initial[station].contains(value) {
+                continue;
+            }
+
+            let at_station = |partner: &Partner| match station {
+                Holder::Station => matches!(partner, Partner::Station),
+                _ => matches!(partner, Partner::TargetStation),
+            };
+            let carrier = encounters
+                .iter()
+                .filter(|encounter| at_station(&encounter.partner) && encounter.start <= *tick)
+                .filter(|encounter| encounter.bot.data_after.contains(value))
+                .max_by_key(|encounter| encounter.start);
+
+            if let Some(carrier) = carrier {
+                sources.insert((*station, *value), (Holder::Bot(carrier.bot.bot_id), *tick));
+            }
+        }
+
+        sources
+    }
+
+    /// Holders that passed `value` on to `holder`, starting at the origin, or at `None` when the
+    /// origin is unknown.
+    fn chain(
+        &self,
+        sources: &BTreeMap<(Holder, u8), (Holder, u64)>,
+        holder: Holder,
+        value: u8,
+    ) -> Vec<Option<Holder>> {
+        let mut chain = vec![holder];
+        let mut seen = BTreeSet::from([holder]);
+
+        let mut origin = true;
+        while let Some((source, _)) = sources.get(&(chain[chain.len() - 1], value)) {
+            if !seen.insert(*source) {
+                origin = false;
+                break;
+            }
+            chain.push(*source);
+        }
+        origin &= self
+            .initial
+            .get(&chain[chain.len() - 1])
+            .is_some_and(|data| data.contains(&value));
+
+        let mut chain: Vec<Option<Holder>> = chain.into_iter().map(Some).collect();
+        if !origin {
+            chain.push(None);
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Hand-overs since the origin, `-` when it is unknown.
+    fn hops(chain: &[Option<Holder>]) -> String {
+        match chain.first() {
+            Some(Some(_)) => (chain.len() - 1).to_string(),
+            _ => "-".to_string(),
+        }
+    }
+
+    fn link(holder: &Option<Holder>) -> String {
+        holder.map_or("Unknown".to_string(), |holder| holder.to_string())
+    }
+
+    fn deliveries(&self, sources: &BTreeMap<(Holder, u8), (Holder, u64)>) -> Table {
+        let delivered = format!("Delivered ({})", self.time.unit.name());
+        let mut table = Table::new(
+            "Data Delivered to the Target Station",
+            &["Data", &delivered, "Hops", "Chain", "Relays"],
+        );
+
+        for ((station, value), tick) in &self.stations {
+            if *station != Holder::TargetStation || self.initial[station].contains(value) {
+                continue;
+            }
+
+            let chain = self.chain(sources, *station, *value);
+            // Bots between the origin and the bot that delivered the value.
+            let relays: Vec<String> = chain
+                .get(1..chain.len().saturating_sub(2))
+                .unwrap_or_default()
+                .iter()
+                .filter(|holder| matches!(holder, Some(Holder::Bot(_))))
+                .map(Self::link)
+                .collect();
+
+            table.push(vec![
+                value.to_string(),
+                self.time.format(*tick as f64),
+                Self::hops(&chain),
+                chain
+                    .iter()
+                    .map(Self::link)
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
+                relays.join(", "),
+            ]);
+        }
+
+        table
+    }
+
+    fn acquisitions(&self, sources: &BTreeMap<(Holder, u8), (Holder, u64)>) -> Table {
+        let received = format!("Received ({})", self.time.unit.name());
+        let mut table = Table::new(
+            "Data Sources",
+            &["Holder", "Data", "Source", &received, "Hops From Origin"],
+        );
+
+        for ((holder, value), (source, tick)) in sources {
+            if let Holder::Bot(bot_id) = holder {
+                if !selected(&self.bot_ids, *bot_id) {
+                    continue;
+                }
+            }
+
+            let chain = self.chain(sources, *holder, *value);
+            table.push(vec![
+                holder.to_string(),
+                value.to_string(),
+                source.to_string(),
+                self.time.format(*tick as f64),
+                Self::hops(&chain),
+            ]);
+        }
+
+        table
+    }
+}
+
+impl Metric for DataProvenance {
+    fn name(&self) -> &'static str {
+        "data-provenance"
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        self.detector.accumulate(frame);
+
+        for record in &frame.bots {
+            self.sample(Holder::Bot(record.bot_id), record);
+        }
+        if let Some(station) = &frame.station {
+            self.sample(Holder::Station, station);
+        }
+        if let Some(target_station) = &frame.target_station {
+            self.sample(Holder::TargetStation, target_station);
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> Vec<Output> {
+        let encounters = std::mem::take(&mut self.detector).finish();
+        let sources = self.sources(&encounters);
+
+        vec![
+            Output::table(self.name(), self.deliveries(&sources)),
+            Output::table(
+                &format!("{}-sources", self.name()),
+                self.acquisitions(&sources),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encounter::Party;
+
+    fn party(bot_id: u16, data_before: &[u8], data_after: &[u8]) -> Party {
+        Party {
+            bot_id,
+            energy_before: 100.0,
+            energy_after: 100.0,
+            data_before: data_before.to_vec(),
+            data_after: data_after.to_vec(),
+        }
+    }
+
+    fn encounter(start: u64, bot: Party, partner: Partner) -> Encounter {
+        Encounter {
+            start,
+            end: start + 2,
+            position: (0.0, 0.0),
+            bot,
+            partner,
+        }
+    }
+
+    /// Data 7 goes from the station through bots 1 and 2 to the target station. Bot 3 got data 8
+    /// from something out of reach and delivers it, bot 4 delivers the data 9 it started with.
+    fn provenance() -> (DataProvenance, Vec<Encounter>) {
+        let provenance = DataProvenance {
+            initial: BTreeMap::from([
+                (Holder::Station, vec![7]),
+                (Holder::TargetStation, vec![]),
+                (Holder::Bot(1), vec![]),
+                (Holder::Bot(2), vec![]),
+                (Holder::Bot(3), vec![]),
+                (Holder::Bot(4), vec![9]),
+            ]),
+            stations: BTreeMap::from([
+                ((Holder::Station, 7), 0),
+                ((Holder::TargetStation, 7), 40),
+                ((Holder::TargetStation, 8), 50),
+                ((Holder::TargetStation, 9), 60),
+            ]),
+            ..Default::default()
+        };
+        let encounters = vec![
+            encounter(10, party(1, &[], &[7]), Partner::Station),
+            encounter(20, party(1, &[7], &[7]), Partner::Bot(party(2, &[], &[7]))),
+            encounter(30, party(3, &[], &[8]), Partner::Unknown),
+            encounter(36, party(2, &[7], &[7]), Partner::TargetStation),
+            encounter(48, party(3, &[8], &[8]), Partner::TargetStation),
+            encounter(58, party(4, &[9], &[9]), Partner::TargetStation),
+        ];
+
+        (provenance, encounters)
+    }
+
+    #[test]
+    fn traces_sources_of_received_data() {
+        let (provenance, encounters) = provenance();
+
+        assert_eq!(
+            provenance.sources(&encounters),
+            BTreeMap::from([
+                ((Holder::Bot(1), 7), (Holder::Station, 12)),
+                ((Holder::Bot(2), 7), (Holder::Bot(1), 22)),
+                ((Holder::TargetStation, 7), (Holder::Bot(2), 40)),
+                ((Holder::TargetStation, 8), (Holder::Bot(3), 50)),
+                ((Holder::TargetStation, 9), (Holder::Bot(4), 60)),
+            ])
+        );
+    }
+
+    #[test]
+    fn lists_relay_chains_to_the_target_station() {
+        let (provenance, encounters) = provenance();
+        let table = provenance.deliveries(&provenance.sources(&encounters));
+
+        assert_eq!(
+            table.rows,
+            vec![
+                [
+                    "7",
+                    "40",
+                    "3",
+                    "Station -> Bot 1 -> Bot 2 -> Target Station",
+                    "Bot 1"
+                ]
+                .map(String::from),
+                ["8", "50", "-", "Unknown -> Bot 3 -> Target Station", ""].map(String::from),
+                ["9", "60", "1", "Bot 4 -> Target Station", ""].map(String::from),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_hops_of_unknown_origins_out() {
+        let (provenance, encounters) = provenance();
+        let table = provenance.acquisitions(&provenance.sources(&encounters));
+
+        let hops: Vec<(&str, &str, &str)> = table
+            .rows
+            .iter()
+            .map(|row| (row[0].as_str(), row[1].as_str(), row[4].as_str()))
+            .collect();
+        assert_eq!(
+            hops,
+            vec![
+                ("Target Station", "7", "3"),
+                ("Target Station", "8", "-"),
+                ("Target Station", "9", "1"),
+                ("Bot 1", "7", "1"),
+                ("Bot 2", "7", "2"),
+            ]
+        );
+    }
+}
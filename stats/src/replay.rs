@@ -0,0 +1,243 @@
+//! Replay logs written by `Logger::CsvLogger` (`logs/*.csv`).
+
+use serde::Deserialize;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectType {
+    Bot,
+    Station,
+    TargetStation,
+}
+
+/// Statuses set on `Simulation::Object::Object#status` by the bot, its tasks and the experimenter.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(from = "String")]
+pub enum BotStatus {
+    Depleted,
+    Abort,
+    ActiveAborting,
+    Active,
+    Trophallaxis,
+    DataTransfer,
+    Unknown(String),
+}
+
+impl BotStatus {
+    pub const KNOWN: [BotStatus; 6] = [
+        BotStatus::Depleted,
+        BotStatus::Abort,
+        BotStatus::ActiveAborting,
+        BotStatus::Active,
+        BotStatus::Trophallaxis,
+        BotStatus::DataTransfer,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Depleted => "depleted",
+            Self::Abort => "abort",
+            Self::ActiveAborting => "active_aborting",
+            Self::Active => "active",
+            Self::Trophallaxis => "trophallaxis",
+            Self::DataTransfer => "data_transfer",
+            Self::Unknown(status) => status,
+        }
+    }
+}
+
+impl From<String> for BotStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "depleted" => Self::Depleted,
+            "abort" => Self::Abort,
+            "active_aborting" => Self::ActiveAborting,
+            "active" => Self::Active,
+            "trophallaxis" => Self::Trophallaxis,
+            "data_transfer" => Self::DataTransfer,
+            _ => Self::Unknown(status),
+        }
+    }
+}
+
+impl std::fmt::Display for BotStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Record {
+    pub tick: u64,
+    pub bot_id: u16,
+    pub energy: f64,
+    #[serde(deserialize_with = "crate::de::data_field")]
+    pub data: Vec<u8>,
+    pub x: f64,
+    pub y: f64,
+    pub vel_x: f64,
+    pub vel_y: f64,
+    pub rotation: f64,
+    pub status: BotStatus,
+    #[serde(deserialize_with = "crate::de::color_field")]
+    pub color: [f64; 4],
+    pub r#type: ObjectType,
+}
+
+impl Record {
+    pub fn is_bot(&self) -> bool {
+        self.r#type == ObjectType::Bot
+    }
+}
+
+/// All rows logged for a single tick.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub tick: u64,
+    pub bots: Vec<Record>,
+    pub station: Option<Record>,
+    pub target_station: Option<Record>,
+}
+
+impl Frame {
+    fn new(tick: u64) -> Self {
+        Frame {
+            tick,
+            bots: Vec::new(),
+            station: None,
+            target_station: None,
+        }
+    }
+
+    fn push(&mut self, record: Record) {
+        match record.r#type {
+            ObjectType::Bot => self.bots.push(record),
+            ObjectType::Station => self.station = Some(record),
+            ObjectType::TargetStation => self.target_station = Some(record),
+        }
+    }
+}
+
+/// Streams a replay log frame by frame, so only a single tick is held in memory at a time.
+///
+/// `CsvLogger` writes every object of a tick on consecutive rows, which is what the grouping relies on.
+pub struct FrameReader<R: Read> {
+    records: csv::DeserializeRecordsIntoIter<R, Record>,
+    pending: Option<Record>,
+}
+
+impl FrameReader<File> {
+    pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::from_reader(File::open(path)?))
+    }
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn from_reader(rdr: R) -> Self {
+        FrameReader {
+            records: csv::Reader::from_reader(rdr).into_deserialize(),
+            pending: None,
+        }
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = Result<Frame, csv::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.pending.take() {
+            Some(record) => record,
+            None => match self.records.next()? {
+                Ok(record) => record,
+                Err(err) => return Some(Err(err)),
+            },
+        };
+
+        let mut frame = Frame::new(first.tick);
+        frame.push(first);
+
+        loop {
+            match self.records.next() {
+                Some(Ok(record)) if record.tick == frame.tick => frame.push(record),
+                Some(Ok(record)) => {
+                    self.pending = Some(record);
+                    break;
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+
+        Some(Ok(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "tick,bot_id,energy,data,x,y,vel_x,vel_y,rotation,status,color,type\n";
+
+    fn row(tick: u64, bot_id: u16, r#type: &str) -> String {
+        format!(
+            "{},{},100.0,\"1,2\",10.0,20.0,0.0,0.0,0.0,active,0.5|0.5|0|1,{}\n",
+            tick, bot_id, r#type
+        )
+    }
+
+    fn frames(rows: &[String]) -> Vec<Frame> {
+        let log = format!("{}{}", HEADER, rows.concat());
+        FrameReader::from_reader(log.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn groups_rows_by_tick() {
+        let frames = frames(&[
+            row(0, 0, "bot"),
+            row(0, 1, "bot"),
+            row(0, 0, "station"),
+            row(0, 0, "target_station"),
+            row(2, 0, "bot"),
+            row(2, 1, "bot"),
+        ]);
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].tick, 0);
+        assert_eq!(frames[0].bots.len(), 2);
+        assert!(frames[0].station.is_some());
+        assert!(frames[0].target_station.is_some());
+        assert_eq!(frames[1].tick, 2);
+        assert_eq!(frames[1].bots[1].bot_id, 1);
+        assert_eq!(frames[1].bots[1].data, vec![1, 2]);
+        assert!(frames[1].station.is_none());
+    }
+
+    #[test]
+    fn keeps_gaps_between_ticks() {
+        let frames = frames(&[row(0, 0, "bot"), row(2, 0, "bot"), row(10, 0, "bot")]);
+
+        let ticks: Vec<u64> = frames.iter().map(|frame| frame.tick).collect();
+        assert_eq!(ticks, vec![0, 2, 10]);
+    }
+
+    #[test]
+    fn splits_ticks_that_are_not_on_consecutive_rows() {
+        let frames = frames(&[row(4, 0, "bot"), row(2, 1, "bot"), row(4, 2, "bot")]);
+
+        let ticks: Vec<u64> = frames.iter().map(|frame| frame.tick).collect();
+        assert_eq!(ticks, vec![4, 2, 4]);
+        assert!(frames.iter().all(|frame| frame.bots.len() == 1));
+    }
+
+    #[test]
+    fn reports_rows_that_do_not_parse() {
+        let log = format!("{}{}0,x,,,,,,,,,,bot\n", HEADER, row(0, 0, "bot"));
+        let mut reader = FrameReader::from_reader(log.as_bytes());
+
+        assert!(reader.next().unwrap().is_err());
+    }
+}
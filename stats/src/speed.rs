@@ -0,0 +1,410 @@
+//! How fast and in which direction bots move, from the logged velocity and rotation, and which
+//! samples break the speed limit of the physics engine.
+
+use std::collections::BTreeMap;
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::aggregate::{plot, selected};
+use crate::constants::{CM_PER_PX, MAX_SPEED};
+use crate::metric::{Metric, Options, Output};
+use crate::plot::{Bin, Color, Layer, Plot, Series};
+use crate::replay::{BotStatus, Frame};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+/// Largest error in a speed computed from velocity components `CsvLogger` rounds to four decimals.
+const ROUNDING: f64 = 0.00005 * SQRT_2;
+
+/// Sectors of the heading rose.
+const SECTORS: usize = 24;
+
+/// Bins of the speed distribution.
+const BINS: usize = 30;
+
+/// Samples over the speed limit printed to the terminal.
+const VIOLATIONS_PRINTED: usize = 20;
+
+#[derive(Debug, Clone)]
+struct Violation {
+    tick: u64,
+    bot_id: u16,
+    status: BotStatus,
+    speed: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bot {
+    last_frame: Option<usize>,
+    /// Times and speeds in pixels per tick, NaN at gaps.
+    times: Vec<f64>,
+    speeds: Vec<f64>,
+    moving: usize,
+    violations: usize,
+}
+
+#[derive(Default)]
+pub struct Speed {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    frames: usize,
+    bots: BTreeMap<u16, Bot>,
+    /// Samples of moving bots per heading sector, counter-clockwise from east.
+    headings: [usize; SECTORS],
+    violations: Vec<Violation>,
+}
+
+impl Speed {
+    pub fn new(options: &Options) -> Self {
+        Speed {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+
+    /// Centimetres per second of a speed in pixels per tick.
+    fn cm_per_second(&self, speed: f64) -> f64 {
+        speed * self.time.ticks_per_second * CM_PER_PX
+    }
+
+    fn moving_speeds(&self) -> impl Iterator<Item = f64> + '_ {
+        self.bots
+            .values()
+            .flat_map(|bot| bot.speeds.iter().copied())
+            .filter(|speed| *speed > 0.0)
+    }
+
+    fn summary(&self) -> Table {
+        let mut table = Table::new(
+            "Speed per Bot",
+            &[
+                "Bot",
+                "Samples",
+                "Moving (%)",
+                "Mean Moving Speed (cm/s)",
+                "Max Speed (cm/s)",
+                "Over Limit",
+            ],
+        );
+
+        for (bot_id, bot) in &self.bots {
+            let speeds: Vec<f64> = bot.speeds.iter().copied().filter(|s| !s.is_nan()).collect();
+            let moving_speed_sum: f64 = speeds.iter().filter(|speed| **speed > 0.0).sum();
+            let max = speeds.iter().copied().fold(0.0, f64::max);
+
+            table.push(vec![
+                bot_id.to_string(),
+                speeds.len().to_string(),
+                format!("{:.1}", bot.moving as f64 / speeds.len() as f64 * 100.0),
+                if bot.moving > 0 {
+                    format!(
+                        "{:.2}",
+                        self.cm_per_second(moving_speed_sum / bot.moving as f64)
+                    )
+                } else {
+                    "-".to_string()
+                },
+                format!("{:.2}", self.cm_per_second(max)),
+                bot.violations.to_string(),
+            ]);
+        }
+
+        table
+    }
+
+    /// Samples over the speed limit, fastest first.
+    fn violations(&self) -> Table {
+        let time = format!("Time ({})", self.time.unit.name());
+        let mut table = Table::new(
+            "Samples Over the Speed Limit, Fastest First",
+            &[&time, "Bot", "Status", "Speed (cm/s)", "Limit (cm/s)"],
+        );
+
+        let mut violations: Vec<&Violation> = self.violations.iter().collect();
+        violations.sort_by(|a, b| b.speed.total_cmp(&a.speed));
+
+        table.limit(VIOLATIONS_PRINTED);
+        for violation in violations {
+            table.push(vec![
+                self.time.format(violation.tick as f64),
+                violation.bot_id.to_string(),
+                violation.status.to_string(),
+                format!("{:.3}", self.cm_per_second(violation.speed)),
+                format!("{:.3}", self.cm_per_second(MAX_SPEED)),
+            ]);
+        }
+
+        table
+    }
+
+    fn distribution(&self) -> Plot {
+        let mut plot = Plot::new(
+            "Speed Distribution of Moving Bots",
+            ("Speed (cm/s)", "Fraction of Samples"),
+        );
+        plot.x.min = Some(0.0);
+        plot.y.min = Some(0.0);
+        plot.legend = false;
+
+        let speeds: Vec<f64> = self
+            .moving_speeds()
+            .map(|speed| self.cm_per_second(speed))
+            .collect();
+        let max = speeds.iter().copied().fold(0.0, f64::max);
+        if speeds.is_empty() || max == 0.0 {
+            return plot;
+        }
+
+        let width = max / BINS as f64;
+        let mut counts = [0usize; BINS];
+        for speed in &speeds {
+            counts[((speed / width) as usize).min(BINS - 1)] += 1;
+        }
+        plot.histogram(
+            "Speed",
+            counts
+                .iter()
+                .enumerate()
+                .map(|(i, count)| Bin {
+                    start: i as f64 * width,
+                    end: (i + 1) as f64 * width,
+                    value: *count as f64 / speeds.len() as f64,
+                })
+                .collect(),
+        );
+
+        let top = *counts.iter().max().expect("No bins") as f64 / speeds.len() as f64 * 1.1;
+        let limit = self.cm_per_second(MAX_SPEED);
+        plot.y.max = Some(top);
+        plot.layers.push(Layer::Line {
+            series: Series {
+                name: "Speed Limit".to_string(),
+                x: vec![limit, limit],
+                y: vec![0.0, top],
+            },
+            color: Some(Color::RED),
+            width: 2.0,
+        });
+
+        plot
+    }
+
+    /// Fraction of moving samples per heading as wedges around the origin, north up.
+    fn rose(&self) -> Plot {
+        let mut plot = Plot::new(
+            "Heading of Moving Bots (Fraction of Samples)",
+            ("West - East", "South - North"),
+        );
+        plot.legend = false;
+        plot.equal_axes = true;
+
+        let total: usize = self.headings.iter().sum();
+        if total == 0 {
+            return plot;
+        }
+
+        let largest = *self.headings.iter().max().expect("No sectors") as f64 / total as f64;
+        plot.x.min = Some(-largest);
+        plot.x.max = Some(largest);
+        plot.y.min = Some(-largest);
+        plot.y.max = Some(largest);
+
+        let sector = 2.0 * PI / SECTORS as f64;
+        for (i, count) in self.headings.iter().enumerate() {
+            let radius = *count as f64 / total as f64;
+            let mut points = vec![(0.0, 0.0)];
+            // A few points along the arc of each wedge.
+            for step in 0..=4 {
+                let angle = (i as f64 - 0.5 + step as f64 / 4.0) * sector;
+                points.push((radius * angle.cos(), radius * angle.sin()));
+            }
+            plot.polygon("", points, Color::BLUE);
+        }
+
+        plot
+    }
+}
+
+impl Metric for Speed {
+    fn name(&self) -> &'static str {
+        "speed"
+    }
+
+    fn title(&self) -> String {
+        "Bot Speed Over Time".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (self.time.label(), "Speed (cm/s)".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let time = self.time.value(frame.tick as f64);
+
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            let speed = record.vel_x.hypot(record.vel_y);
+            let bot = self.bots.entry(record.bot_id).or_default();
+            if bot.last_frame.is_some_and(|last| last + 1 < self.frames) {
+                bot.times.push(f64::NAN);
+                bot.speeds.push(f64::NAN);
+            }
+            bot.last_frame = Some(self.frames);
+            bot.times.push(time);
+            bot.speeds.push(speed);
+
+            if speed > 0.0 {
+                bot.moving += 1;
+                // `rotation` is clockwise from east on the screen, so counter-clockwise upright.
+                let heading = (360.0 - record.rotation).rem_euclid(360.0);
+                let sector = (heading / 360.0 * SECTORS as f64 + 0.5) as usize % SECTORS;
+                self.headings[sector] += 1;
+            }
+
+            if speed > MAX_SPEED + ROUNDING {
+                bot.violations += 1;
+                self.violations.push(Violation {
+                    tick: frame.tick,
+                    bot_id: record.bot_id,
+                    status: record.status.clone(),
+                    speed,
+                });
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let mut plot = plot(&*self);
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+
+        for (bot_id, bot) in &self.bots {
+            plot.line(Series {
+                name: format!("Bot {}", bot_id),
+                x: bot.times.clone(),
+                y: bot.speeds.iter().map(|s| self.cm_per_second(*s)).collect(),
+            });
+        }
+        if let (Some(first), Some(last)) = (
+            self.bots
+                .values()
+                .filter_map(|bot| bot.times.first())
+                .copied()
+                .reduce(f64::min),
+            self.bots
+                .values()
+                .filter_map(|bot| bot.times.last())
+                .copied()
+                .reduce(f64::max),
+        ) {
+            let limit = self.cm_per_second(MAX_SPEED);
+            plot.layers.push(Layer::Line {
+                series: Series {
+                    name: "Speed Limit".to_string(),
+                    x: vec![first, last],
+                    y: vec![limit, limit],
+                },
+                color: Some(Color::RED),
+                width: 2.0,
+            });
+        }
+
+        vec![
+            Output::table(self.name(), self.summary()),
+            Output::table(&format!("{}-violations", self.name()), self.violations()),
+            Output::plot(self.name(), plot),
+            Output::plot(
+                &format!("{}-distribution", self.name()),
+                self.distribution(),
+            ),
+            Output::plot(&format!("{}-heading-rose", self.name()), self.rose()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TICKS_PER_SECOND;
+    use crate::replay::{ObjectType, Record};
+
+    fn record(bot_id: u16, (vel_x, vel_y): (f64, f64)) -> Record {
+        Record {
+            tick: 10,
+            bot_id,
+            energy: 100.0,
+            data: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            vel_x,
+            vel_y,
+            rotation: 0.0,
+            status: BotStatus::Active,
+            color: [0.0; 4],
+            r#type: ObjectType::Bot,
+        }
+    }
+
+    fn speed(velocities: &[(f64, f64)]) -> Speed {
+        let mut speed = Speed::default();
+        speed.accumulate(&Frame {
+            tick: 10,
+            bots: velocities
+                .iter()
+                .enumerate()
+                .map(|(bot_id, velocity)| record(bot_id as u16, *velocity))
+                .collect(),
+            station: None,
+            target_station: None,
+        });
+        speed
+    }
+
+    #[test]
+    fn tolerates_the_rounding_of_the_log() {
+        // Bots at the limit in every heading, with the components rounded like `CsvLogger` does.
+        let round = |v: f64| (v * 10000.0).round() / 10000.0;
+        let velocities: Vec<(f64, f64)> = (0..360)
+            .map(|degrees| (degrees as f64).to_radians().sin_cos())
+            .map(|(sin, cos)| (round(MAX_SPEED * cos), round(MAX_SPEED * sin)))
+            .collect();
+        assert!(velocities.iter().any(|(x, y)| x.hypot(*y) > MAX_SPEED));
+        assert!(speed(&velocities).violations.is_empty());
+
+        let speed = speed(&[
+            (MAX_SPEED, 0.0),
+            (0.0, -(MAX_SPEED + 0.9 * ROUNDING)),
+            (MAX_SPEED + 2.0 * ROUNDING, 0.0),
+        ]);
+        let flagged: Vec<u16> = speed.violations.iter().map(|v| v.bot_id).collect();
+        assert_eq!(flagged, vec![2]);
+        assert_eq!(speed.bots[&2].violations, 1);
+        assert_eq!(speed.bots[&0].violations, 0);
+    }
+
+    #[test]
+    fn lists_violations_in_centimetres_per_second() {
+        let speed = speed(&[(2.0 * MAX_SPEED, 0.0), (0.0, 0.0), (0.0, 3.0 * MAX_SPEED)]);
+        let table = speed.violations();
+        let limit = MAX_SPEED * TICKS_PER_SECOND * CM_PER_PX;
+
+        let speeds: Vec<(&str, &str)> = table
+            .rows
+            .iter()
+            .map(|row| (row[1].as_str(), row[3].as_str()))
+            .collect();
+        assert_eq!(
+            speeds,
+            vec![
+                ("2", format!("{:.3}", 3.0 * limit).as_str()),
+                ("0", format!("{:.3}", 2.0 * limit).as_str()),
+            ]
+        );
+        assert_eq!(table.rows[0][4], format!("{:.3}", limit));
+    }
+}
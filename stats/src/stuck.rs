@@ -0,0 +1,590 @@
+//! Bots that are free to drive but get nowhere, and where bots run into things.
+//!
+//! A bot is stuck while it is free to drive and, over the last stretch of the window, either stays
+//! within a small distance of where the stretch began or turns around again and again, as bots do
+//! when they are trapped in a corner. Free are `active` and `active_aborting`, the statuses
+//! `Bot#busy?` is false for: an aborting bot drives away from what it hit, and a trapped bot keeps
+//! aborting collisions with the walls around it. Consecutive stuck samples make one interval, which
+//! starts no earlier than the bot's last one ended and is put down to the reason most of its samples
+//! were stuck for.
+//!
+//! The log has no collisions of its own, but the tasks a collision starts show in the status: an
+//! `AbortCollisionTask` or a declined exchange takes an `active` bot straight to `active_aborting`,
+//! and a `CollisionTask` that exchanges energy or data puts it in `trophallaxis` or `data_transfer`.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::aggregate::{draw_map, selected};
+use crate::encounter::in_contact;
+use crate::map::Map;
+use crate::metric::{Metric, Options, Output};
+use crate::movement::turn;
+use crate::occupancy::{cell, grid_heatmap, Cell, OccupancyArgs};
+use crate::plot::Plot;
+use crate::replay::{BotStatus, Frame};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+// Options of the stuck bot detection (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq)]
+pub struct StuckArgs {
+    /// Length in seconds of the stretch of track a stuck bot is detected over
+    #[arg(long, default_value_t = 10.0)]
+    pub stuck_window: f64,
+    /// Largest distance in pixels a stuck bot moves away from where the stretch began
+    #[arg(long, default_value_t = 5.0)]
+    pub stuck_distance: f64,
+    /// Fewest turns of more than 90 degrees in the stretch that make a bot stuck
+    #[arg(long, default_value_t = 3)]
+    pub stuck_reversals: usize,
+}
+
+impl Default for StuckArgs {
+    fn default() -> Self {
+        StuckArgs {
+            stuck_window: 10.0,
+            stuck_distance: 5.0,
+            stuck_reversals: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reason {
+    /// Barely moved.
+    Immobile,
+    /// Kept reversing direction.
+    Reversing,
+}
+
+/// Whether a bot in `status` drives.
+fn drives(status: &BotStatus) -> bool {
+    matches!(status, BotStatus::Active | BotStatus::ActiveAborting)
+}
+
+impl Reason {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Immobile => "immobile",
+            Self::Reversing => "reversing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    bot_id: u16,
+    start: u64,
+    end: u64,
+    /// Sums of the positions of the stuck samples, for the mean location.
+    x: f64,
+    y: f64,
+    /// Stuck samples for each reason.
+    immobile: usize,
+    reversing: usize,
+}
+
+impl Interval {
+    fn samples(&self) -> usize {
+        self.immobile + self.reversing
+    }
+
+    fn reason(&self) -> Reason {
+        if self.reversing > self.immobile {
+            Reason::Reversing
+        } else {
+            Reason::Immobile
+        }
+    }
+
+    fn add(&mut self, tick: u64, x: f64, y: f64, reason: Reason) {
+        self.end = tick;
+        self.x += x;
+        self.y += y;
+        match reason {
+            Reason::Immobile => self.immobile += 1,
+            Reason::Reversing => self.reversing += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    tick: u64,
+    x: f64,
+    y: f64,
+    reversal: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bot {
+    last: Option<(usize, u64, BotStatus)>,
+    /// Tick the bot became free to drive, `None` while it is not.
+    since: Option<u64>,
+    window: VecDeque<Sample>,
+    heading: Option<f64>,
+    interval: Option<Interval>,
+    /// End of the bot's last interval.
+    stuck_until: u64,
+    aborted: usize,
+    exchanges: usize,
+}
+
+#[derive(Default)]
+pub struct Stuck {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    map: Option<Map>,
+    args: StuckArgs,
+    grid_size: f64,
+    frames: usize,
+    bots: BTreeMap<u16, Bot>,
+    intervals: Vec<Interval>,
+    /// Seconds stuck and collisions per cell.
+    stuck: HashMap<Cell, f64>,
+    collisions: HashMap<Cell, f64>,
+}
+
+impl Stuck {
+    pub fn new(options: &Options) -> Self {
+        Stuck {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            map: options.map.clone(),
+            args: options.args(),
+            grid_size: options.args::<OccupancyArgs>().grid_size,
+            ..Default::default()
+        }
+    }
+
+    fn close(&mut self, bot_id: u16) {
+        let bot = self.bots.get_mut(&bot_id).expect("Unknown bot");
+        bot.since = None;
+        bot.window.clear();
+        bot.heading = None;
+        if let Some(interval) = bot.interval.take() {
+            bot.stuck_until = interval.end;
+            self.intervals.push(interval);
+        }
+    }
+
+    fn hotspots(&self, title: &str, cells: &HashMap<Cell, f64>, label: &str) -> Plot {
+        let mut plot = Plot::new(title, ("X Coordinate", "Y Coordinate"));
+        if let Some(heatmap) = grid_heatmap(cells, self.grid_size, self.map.as_ref(), label) {
+            plot.heatmap(heatmap);
+        }
+        if let Some(map) = &self.map {
+            draw_map(&mut plot, map);
+        }
+
+        plot
+    }
+
+    fn intervals(&self) -> Table {
+        let unit = self.time.unit.name();
+        let start = format!("Start ({})", unit);
+        let end = format!("End ({})", unit);
+        let duration = format!("Duration ({})", unit);
+        let mut table = Table::new(
+            "Stuck Bots",
+            &["Bot", &start, &end, &duration, "X", "Y", "Reason"],
+        );
+
+        for interval in &self.intervals {
+            table.push(vec![
+                interval.bot_id.to_string(),
+                self.time.format(interval.start as f64),
+                self.time.format(interval.end as f64),
+                self.time.format((interval.end - interval.start) as f64),
+                format!("{:.1}", interval.x / interval.samples() as f64),
+                format!("{:.1}", interval.y / interval.samples() as f64),
+                interval.reason().name().to_string(),
+            ]);
+        }
+
+        table
+    }
+
+    fn summary(&self) -> Table {
+        let stuck = format!("Time Stuck ({})", self.time.unit.name());
+        let mut table = Table::new(
+            "Collisions and Stuck Bots",
+            &[
+                "Bot",
+                "Aborted Collisions",
+                "Exchanges",
+                "Times Stuck",
+                &stuck,
+            ],
+        );
+
+        for (bot_id, bot) in &self.bots {
+            let intervals = self
+                .intervals
+                .iter()
+                .filter(|interval| interval.bot_id == *bot_id);
+            table.push(vec![
+                bot_id.to_string(),
+                bot.aborted.to_string(),
+                bot.exchanges.to_string(),
+                intervals.clone().count().to_string(),
+                self.time.format(
+                    intervals
+                        .map(|interval| interval.end - interval.start)
+                        .sum::<u64>() as f64,
+                ),
+            ]);
+        }
+
+        table
+    }
+}
+
+impl Metric for Stuck {
+    fn name(&self) -> &'static str {
+        "stuck"
+    }
+
+    fn title(&self) -> String {
+        "Where Bots Get Stuck".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        ("X Coordinate".to_string(), "Y Coordinate".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        let window = self.time.ticks(self.args.stuck_window);
+
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            let free = drives(&record.status);
+            let bot = self.bots.entry(record.bot_id).or_default();
+            let last = bot
+                .last
+                .replace((self.frames, frame.tick, record.status.clone()));
+            let previous = last.filter(|(frame, _, _)| frame + 1 == self.frames);
+
+            if let Some((_, _, status)) = &previous {
+                let from_free = drives(status);
+                let collided =
+                    if *status == BotStatus::Active && record.status == BotStatus::ActiveAborting {
+                        bot.aborted += 1;
+                        true
+                    } else if from_free && in_contact(&record.status) {
+                        bot.exchanges += 1;
+                        true
+                    } else {
+                        false
+                    };
+                if collided {
+                    *self
+                        .collisions
+                        .entry(cell(record.x, record.y, self.grid_size))
+                        .or_default() += 1.0;
+                }
+            }
+
+            if !free || previous.is_none() {
+                self.close(record.bot_id);
+                if !free {
+                    continue;
+                }
+            }
+
+            let bot = self.bots.get_mut(&record.bot_id).expect("Unknown bot");
+            let since = *bot.since.get_or_insert(frame.tick);
+
+            let moving = record.vel_x != 0.0 || record.vel_y != 0.0;
+            let reversal = moving
+                && bot
+                    .heading
+                    .is_some_and(|last| turn(last, record.rotation).abs() > 90.0);
+            if moving {
+                bot.heading = Some(record.rotation);
+            }
+
+            bot.window.push_back(Sample {
+                tick: frame.tick,
+                x: record.x,
+                y: record.y,
+                reversal,
+            });
+            while bot
+                .window
+                .front()
+                .is_some_and(|sample| (frame.tick - sample.tick) as f64 > window)
+            {
+                bot.window.pop_front();
+            }
+
+            let start = bot.window[0];
+            let reason = if ((frame.tick - since) as f64) < window {
+                None
+            } else if bot
+                .window
+                .iter()
+                .all(|s| (s.x - start.x).hypot(s.y - start.y) <= self.args.stuck_distance)
+            {
+                Some(Reason::Immobile)
+            } else if bot.window.iter().filter(|s| s.reversal).count() >= self.args.stuck_reversals
+            {
+                Some(Reason::Reversing)
+            } else {
+                None
+            };
+
+            // Seconds stuck since the last sample, or since the stretch began for a new interval.
+            let stuck = match (reason, &mut bot.interval) {
+                (Some(reason), Some(interval)) => {
+                    let seconds = self.time.seconds((frame.tick - interval.end) as f64);
+                    interval.add(frame.tick, record.x, record.y, reason);
+                    seconds
+                }
+                (Some(reason), None) => {
+                    let start = start.tick.max(bot.stuck_until);
+                    let mut interval = Interval {
+                        bot_id: record.bot_id,
+                        start,
+                        end: start,
+                        x: 0.0,
+                        y: 0.0,
+                        immobile: 0,
+                        reversing: 0,
+                    };
+                    interval.add(frame.tick, record.x, record.y, reason);
+                    bot.interval = Some(interval);
+                    self.time.seconds((frame.tick - start) as f64)
+                }
+                (None, _) => {
+                    if let Some(interval) = bot.interval.take() {
+                        bot.stuck_until = interval.end;
+                        self.intervals.push(interval);
+                    }
+                    0.0
+                }
+            };
+            if reason.is_some() {
+                *self
+                    .stuck
+                    .entry(cell(record.x, record.y, self.grid_size))
+                    .or_default() += stuck;
+            }
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(mut self: Box<Self>) -> Vec<Output> {
+        let bot_ids: Vec<u16> = self.bots.keys().copied().collect();
+        for bot_id in bot_ids {
+            self.close(bot_id);
+        }
+        self.intervals
+            .sort_by_key(|interval| (interval.start, interval.bot_id));
+
+        vec![
+            Output::table(self.name(), self.intervals()),
+            Output::table(&format!("{}-per-bot", self.name()), self.summary()),
+            Output::plot(
+                self.name(),
+                self.hotspots(&self.title(), &self.stuck, "Time stuck (s)"),
+            ),
+            Output::plot(
+                &format!("{}-collision-hotspots", self.name()),
+                self.hotspots("Where Bots Collide", &self.collisions, "Collisions"),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{ObjectType, Record};
+
+    /// Position, rotation, whether the bot moves and its status; `None` where it is missing.
+    type Step = Option<(f64, f64, f64, bool, BotStatus)>;
+
+    /// A second window and 10 px cells.
+    fn stuck(steps: &[Step]) -> Stuck {
+        let mut stuck = Stuck {
+            args: StuckArgs {
+                stuck_window: 1.0,
+                ..Default::default()
+            },
+            grid_size: 10.0,
+            ..Default::default()
+        };
+        for (i, step) in steps.iter().enumerate() {
+            let tick = i as u64 * 2;
+            stuck.accumulate(&Frame {
+                tick,
+                bots: step
+                    .iter()
+                    .map(|(x, y, rotation, moving, status)| Record {
+                        tick,
+                        bot_id: 0,
+                        energy: 100.0,
+                        data: Vec::new(),
+                        x: *x,
+                        y: *y,
+                        vel_x: if *moving { 0.5 } else { 0.0 },
+                        vel_y: 0.0,
+                        rotation: *rotation,
+                        status: status.clone(),
+                        color: [0.0; 4],
+                        r#type: ObjectType::Bot,
+                    })
+                    .collect(),
+                station: None,
+                target_station: None,
+            });
+        }
+        let bot_ids: Vec<u16> = stuck.bots.keys().copied().collect();
+        for bot_id in bot_ids {
+            stuck.close(bot_id);
+        }
+        stuck
+    }
+
+    fn standing(x: f64) -> Step {
+        Some((x, 5.0, 0.0, false, BotStatus::Active))
+    }
+
+    /// Driving east a pixel per sample, turned around `flips` times.
+    fn driving(i: usize, flips: usize) -> Step {
+        let rotation = 180.0 * (flips % 2) as f64;
+        Some((i as f64, 5.0, rotation, true, BotStatus::Active))
+    }
+
+    fn spans(stuck: &Stuck) -> Vec<(u64, u64, Reason)> {
+        stuck
+            .intervals
+            .iter()
+            .map(|interval| (interval.start, interval.end, interval.reason()))
+            .collect()
+    }
+
+    fn seconds_stuck(stuck: &Stuck) -> f64 {
+        stuck.stuck.values().sum()
+    }
+
+    #[test]
+    fn finds_immobile_bots_from_the_start_of_the_stretch() {
+        // Standing for 2 seconds, then driving off.
+        let steps: Vec<Step> = (0..100)
+            .map(|i| if i < 60 { standing(2.0) } else { driving(i, 0) })
+            .collect();
+        let stuck = stuck(&steps);
+
+        // Stuck once the window is full, counted from its first sample.
+        assert_eq!(spans(&stuck), vec![(0, 118, Reason::Immobile)]);
+        assert!((seconds_stuck(&stuck) - 118.0 / 60.0).abs() < 1e-9);
+        assert_eq!(stuck.stuck.keys().collect::<Vec<_>>(), vec![&(0, 0)]);
+    }
+
+    #[test]
+    fn finds_bots_that_keep_reversing() {
+        let steps: Vec<Step> = (0..60).map(|i| driving(i, i / 5)).collect();
+        let stuck = stuck(&steps);
+
+        assert_eq!(spans(&stuck), vec![(0, 118, Reason::Reversing)]);
+    }
+
+    #[test]
+    fn puts_an_interval_down_to_its_most_common_reason() {
+        // Standing for a second and a half, turning around on the spot, then reversing 8 px back
+        // and forth for longer.
+        let steps: Vec<Step> = (0..45)
+            .map(|_| standing(2.0))
+            .chain((0..60).map(|i| {
+                let reach = if i < 10 { 1.0 } else { 8.0 };
+                let rotation = 180.0 * (i % 2) as f64;
+                Some((
+                    2.0 + reach * (i % 2) as f64,
+                    5.0,
+                    rotation,
+                    true,
+                    BotStatus::Active,
+                ))
+            }))
+            .collect();
+        let stuck = stuck(&steps);
+
+        let [interval] = stuck.intervals[..] else {
+            panic!("Expected one interval");
+        };
+        assert_eq!((interval.start, interval.end), (0, 208));
+        assert_eq!((interval.immobile, interval.reversing), (26, 49));
+        assert_eq!(interval.reason(), Reason::Reversing);
+    }
+
+    #[test]
+    fn ends_intervals_at_gaps_and_busy_statuses() {
+        let steps: Vec<Step> = (0..150)
+            .map(|i| match i {
+                50 => None,
+                100 => Some((2.0, 5.0, 0.0, false, BotStatus::Trophallaxis)),
+                _ => standing(2.0),
+            })
+            .collect();
+        let stuck = stuck(&steps);
+
+        // After a gap or a busy sample the window starts over.
+        assert_eq!(
+            spans(&stuck),
+            vec![
+                (0, 98, Reason::Immobile),
+                (102, 198, Reason::Immobile),
+                (202, 298, Reason::Immobile),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_counts_time_twice() {
+        // Turned around at samples 10, 20 and 30, stuck until the first turn leaves the window at
+        // sample 41 and again from the turn at sample 45, whose stretch reaches back to sample 15.
+        let flips = |i: usize| [10, 20, 30, 45].iter().filter(|flip| **flip <= i).count();
+        let steps: Vec<Step> = (0..61).map(|i| driving(i, flips(i))).collect();
+        let stuck = stuck(&steps);
+
+        assert_eq!(
+            spans(&stuck),
+            vec![(0, 80, Reason::Reversing), (80, 100, Reason::Reversing)]
+        );
+        let total: u64 = stuck.intervals.iter().map(|i| i.end - i.start).sum();
+        assert!(total <= 120);
+        assert!((seconds_stuck(&stuck) - total as f64 / 60.0).abs() < 1e-9);
+
+        let summary = stuck.summary();
+        assert_eq!(summary.rows[0][3..], ["2", "100"].map(String::from));
+    }
+
+    #[test]
+    fn counts_collisions_from_status_changes() {
+        let at = |status: BotStatus| Some((25.0, 5.0, 0.0, false, status));
+        let stuck = stuck(&[
+            at(BotStatus::Active),
+            at(BotStatus::ActiveAborting),
+            at(BotStatus::ActiveAborting),
+            at(BotStatus::Active),
+            at(BotStatus::Trophallaxis),
+            at(BotStatus::Trophallaxis),
+            at(BotStatus::ActiveAborting),
+            at(BotStatus::DataTransfer),
+            None,
+            at(BotStatus::ActiveAborting),
+            at(BotStatus::Abort),
+            at(BotStatus::ActiveAborting),
+        ]);
+
+        let bot = &stuck.bots[&0];
+        assert_eq!((bot.aborted, bot.exchanges), (1, 2));
+        assert_eq!(stuck.collisions, HashMap::from([((2, 0), 3.0)]));
+    }
+}
@@ -0,0 +1,92 @@
+use capbot_stats::plot::{PlotArgs, PALETTE};
+use capbot_stats::replay::FrameReader;
+use capbot_stats::survival::{self, Lifetimes, Outcome, SurvivalArgs};
+use capbot_stats::table::Table;
+use capbot_stats::time::TimeBase;
+use clap::Parser;
+use std::path::Path;
+
+/// Compares how long bots last in several replay logs with Kaplan–Meier survival curves
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    #[arg(long, required = true, num_args = 1.., value_delimiter = ' ')]
+    log_files: Vec<String>,
+    /// Legend names of the logs, the file names when left out
+    #[arg(long, num_args = 1.., value_delimiter = ',')]
+    names: Vec<String>,
+    #[arg(long, value_delimiter = ' ', num_args = 1..)]
+    bots: Vec<u16>,
+    #[command(flatten)]
+    time: TimeBase,
+    #[command(flatten)]
+    survival: SurvivalArgs,
+    #[command(flatten)]
+    plot: PlotArgs,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if !args.names.is_empty() && args.names.len() != args.log_files.len() {
+        return Err("--names needs a name for every log file".into());
+    }
+    let renderer = args.plot.renderer()?;
+
+    let mut plot = survival::survival_plot("Survival of Bots", &args.time);
+    let median = format!("Median Lifetime ({})", args.time.unit.name());
+    let mut summary = Table::new(
+        "Survival per Log",
+        &["Log", "Bots", "Depleted", "Killed", "Censored", &median],
+    );
+
+    for (idx, log_file) in args.log_files.iter().enumerate() {
+        let name = args.names.get(idx).cloned().unwrap_or_else(|| {
+            Path::new(log_file)
+                .file_stem()
+                .map_or(log_file.clone(), |stem| stem.to_string_lossy().to_string())
+        });
+        println!("Processing file: {} ({})", log_file, name);
+
+        let mut lifetimes = Lifetimes::new(&args.bots);
+        for frame in FrameReader::open(log_file)? {
+            lifetimes.accumulate(&frame?);
+        }
+        let lifetimes = lifetimes.finish();
+        let steps = survival::kaplan_meier(&lifetimes, args.survival);
+
+        let count = |outcome: Outcome| {
+            lifetimes
+                .iter()
+                .filter(|lifetime| lifetime.outcome == outcome)
+                .count()
+                .to_string()
+        };
+        summary.push(vec![
+            name.clone(),
+            lifetimes.len().to_string(),
+            count(Outcome::Depleted),
+            count(Outcome::Killed),
+            count(Outcome::Censored),
+            survival::median_lifetime(&steps)
+                .map_or("-".to_string(), |ticks| args.time.format(ticks as f64)),
+        ]);
+
+        survival::draw(
+            &mut plot,
+            &name,
+            &steps,
+            &args.time,
+            PALETTE[idx % PALETTE.len()],
+        );
+    }
+
+    summary.print();
+    if let Some(output_dir) = &args.plot.output_dir {
+        let path = output_dir.join("survival.csv");
+        summary.write_csv(&path)?;
+        println!("Saved {}", path.display());
+    }
+    renderer.render("survival", &plot)?;
+
+    Ok(())
+}
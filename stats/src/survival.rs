@@ -0,0 +1,513 @@
+//! How long bots last before they are depleted, as Kaplan–Meier survival curves.
+//!
+//! A bot's lifetime runs from its first sample to the first sample it is `depleted` in. Bots that
+//! are still running at their last sample are right-censored there. Bots killed by the experimenter
+//! drop to zero energy at once, which tells them apart from bots that ran out of energy.
+
+use std::collections::BTreeMap;
+
+use crate::aggregate::selected;
+use crate::metric::{Metric, Options, Output};
+use crate::plot::{Color, Layer, Plot, Series};
+use crate::replay::{BotStatus, Frame};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+/// Two-sided 95% quantile of the normal distribution.
+const Z_95: f64 = 1.959964;
+
+// Options of the survival analysis (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq, Default)]
+pub struct SurvivalArgs {
+    /// Count killed bots as censored instead of depleted, to only measure running out of energy
+    #[arg(long)]
+    pub censor_kills: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Depleted,
+    Killed,
+    /// Still running at the bot's last sample.
+    Censored,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Depleted => "depleted",
+            Self::Killed => "killed",
+            Self::Censored => "censored",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Lifetime {
+    pub bot_id: u16,
+    pub start: u64,
+    pub end: u64,
+    pub outcome: Outcome,
+}
+
+impl Lifetime {
+    pub fn duration(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Collects the lifetime of every bot from the frames of a log.
+#[derive(Debug, Clone, Default)]
+pub struct Lifetimes {
+    bot_ids: Vec<u16>,
+    bots: BTreeMap<u16, Lifetime>,
+}
+
+impl Lifetimes {
+    pub fn new(bot_ids: &[u16]) -> Self {
+        Lifetimes {
+            bot_ids: bot_ids.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    pub fn accumulate(&mut self, frame: &Frame) {
+        for record in &frame.bots {
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            let lifetime = self.bots.entry(record.bot_id).or_insert(Lifetime {
+                bot_id: record.bot_id,
+                start: frame.tick,
+                end: frame.tick,
+                outcome: Outcome::Censored,
+            });
+            if lifetime.outcome != Outcome::Censored {
+                continue;
+            }
+
+            lifetime.end = frame.tick;
+            if record.status == BotStatus::Depleted {
+                lifetime.outcome = if record.energy == 0.0 {
+                    Outcome::Killed
+                } else {
+                    Outcome::Depleted
+                };
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<Lifetime> {
+        self.bots.into_values().collect()
+    }
+}
+
+/// A step of a Kaplan–Meier curve, at a lifetime in ticks some bots were depleted or censored at.
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    pub time: u64,
+    pub at_risk: usize,
+    pub events: usize,
+    pub censored: usize,
+    pub survival: f64,
+    /// 95% confidence interval from Greenwood's variance, clamped to `[0, 1]`.
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Kaplan–Meier estimate of the fraction of bots still running after each lifetime in `lifetimes`.
+pub fn kaplan_meier(lifetimes: &[Lifetime], args: SurvivalArgs) -> Vec<Step> {
+    let event = |lifetime: &Lifetime| match lifetime.outcome {
+        Outcome::Depleted => true,
+        Outcome::Killed => !args.censor_kills,
+        Outcome::Censored => false,
+    };
+
+    let mut times: BTreeMap<u64, (usize, usize)> = BTreeMap::new();
+    for lifetime in lifetimes {
+        let (events, censored) = times.entry(lifetime.duration()).or_default();
+        if event(lifetime) {
+            *events += 1;
+        } else {
+            *censored += 1;
+        }
+    }
+
+    let mut at_risk = lifetimes.len();
+    let mut survival = 1.0;
+    let mut greenwood = 0.0;
+    let mut steps = Vec::new();
+
+    for (time, (events, censored)) in times {
+        if events > 0 {
+            survival *= 1.0 - events as f64 / at_risk as f64;
+            if events < at_risk {
+                greenwood += events as f64 / (at_risk * (at_risk - events)) as f64;
+            }
+        }
+
+        let margin = Z_95 * survival * greenwood.sqrt();
+        steps.push(Step {
+            time,
+            at_risk,
+            events,
+            censored,
+            survival,
+            lower: (survival - margin).max(0.0),
+            upper: (survival + margin).min(1.0),
+        });
+
+        at_risk -= events + censored;
+    }
+
+    steps
+}
+
+/// Smallest lifetime at which at most half of the bots are still running.
+pub fn median_lifetime(steps: &[Step]) -> Option<u64> {
+    steps
+        .iter()
+        .find(|step| step.survival <= 0.5)
+        .map(|step| step.time)
+}
+
+/// Draws a survival curve as steps with its confidence band and a mark at every censored lifetime.
+pub fn draw(plot: &mut Plot, name: &str, steps: &[Step], time: &TimeBase, color: Color) {
+    let mut curve = Series {
+        name: name.to_string(),
+        x: vec![0.0],
+        y: vec![1.0],
+    };
+    let mut band = vec![(0.0, 1.0)];
+    let mut lower = vec![(0.0, 1.0)];
+    let mut censored = Series {
+        name: String::new(),
+        x: Vec::new(),
+        y: Vec::new(),
+    };
+
+    let (mut previous, mut previous_lower) = (1.0, 1.0);
+    for step in steps {
+        let x = time.value(step.time as f64);
+        curve.x.extend([x, x]);
+        curve.y.extend([previous, step.survival]);
+        band.extend([(x, band[band.len() - 1].1), (x, step.upper)]);
+        lower.extend([(x, previous_lower), (x, step.lower)]);
+        if step.censored > 0 {
+            censored.x.push(x);
+            censored.y.push(step.survival);
+        }
+
+        previous = step.survival;
+        previous_lower = step.lower;
+    }
+
+    band.extend(lower.into_iter().rev());
+    plot.polygon("", band, color);
+    plot.layers.push(Layer::Line {
+        series: curve,
+        color: Some(color),
+        width: 2.0,
+    });
+    plot.layers.push(Layer::Scatter {
+        series: censored,
+        color: Some(color),
+        size: 1.5,
+    });
+}
+
+/// Lifetime and outcome of every bot.
+pub fn lifetimes_table(lifetimes: &[Lifetime], time: &TimeBase) -> Table {
+    let unit = time.unit.name();
+    let start = format!("Start ({})", unit);
+    let end = format!("End ({})", unit);
+    let lifetime = format!("Lifetime ({})", unit);
+    let mut table = Table::new(
+        "Bot Lifetimes",
+        &["Bot", &start, &end, &lifetime, "Outcome"],
+    );
+
+    for bot in lifetimes {
+        table.push(vec![
+            bot.bot_id.to_string(),
+            time.format(bot.start as f64),
+            time.format(bot.end as f64),
+            time.format(bot.duration() as f64),
+            bot.outcome.name().to_string(),
+        ]);
+    }
+
+    table
+}
+
+/// The steps of a Kaplan–Meier curve.
+pub fn steps_table(steps: &[Step], time: &TimeBase) -> Table {
+    let lifetime = format!("Lifetime ({})", time.unit.name());
+    let mut table = Table::new(
+        "Kaplan-Meier Survival",
+        &[
+            &lifetime,
+            "At Risk",
+            "Depleted",
+            "Censored",
+            "Survival",
+            "Lower 95%",
+            "Upper 95%",
+        ],
+    );
+
+    for step in steps {
+        table.push(vec![
+            time.format(step.time as f64),
+            step.at_risk.to_string(),
+            step.events.to_string(),
+            step.censored.to_string(),
+            format!("{:.3}", step.survival),
+            format!("{:.3}", step.lower),
+            format!("{:.3}", step.upper),
+        ]);
+    }
+
+    table
+}
+
+/// An empty survival plot over lifetimes in the unit of `time`.
+pub fn survival_plot(title: &str, time: &TimeBase) -> Plot {
+    let mut plot = Plot::new(
+        title,
+        (
+            &format!("Lifetime ({})", time.unit.name()),
+            "Fraction of Bots Running",
+        ),
+    );
+    plot.x.min = Some(0.0);
+    plot.x.clock = time.clock();
+    plot.y.min = Some(0.0);
+    plot.y.max = Some(1.0);
+
+    plot
+}
+
+#[derive(Default)]
+pub struct Survival {
+    time: TimeBase,
+    args: SurvivalArgs,
+    lifetimes: Lifetimes,
+}
+
+impl Survival {
+    pub fn new(options: &Options) -> Self {
+        Survival {
+            time: options.time,
+            args: options.args(),
+            lifetimes: Lifetimes::new(&options.bot_ids),
+        }
+    }
+}
+
+impl Metric for Survival {
+    fn name(&self) -> &'static str {
+        "survival"
+    }
+
+    fn title(&self) -> String {
+        "Survival of Bots".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        (
+            format!("Lifetime ({})", self.time.unit.name()),
+            "Fraction of Bots Running".to_string(),
+        )
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        self.lifetimes.accumulate(frame);
+    }
+
+    fn finish(mut self: Box<Self>) -> Vec<Output> {
+        let lifetimes = std::mem::take(&mut self.lifetimes).finish();
+        let steps = kaplan_meier(&lifetimes, self.args);
+
+        let mut plot = survival_plot(&self.title(), &self.time);
+        plot.legend = false;
+        draw(&mut plot, "Bots", &steps, &self.time, Color::BLUE);
+
+        vec![
+            Output::table(self.name(), steps_table(&steps, &self.time)),
+            Output::table(
+                &format!("{}-lifetimes", self.name()),
+                lifetimes_table(&lifetimes, &self.time),
+            ),
+            Output::plot(self.name(), plot),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{ObjectType, Record};
+
+    fn lifetime(bot_id: u16, end: u64, outcome: Outcome) -> Lifetime {
+        Lifetime {
+            bot_id,
+            start: 0,
+            end,
+            outcome,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn kaplan_meier_of_five_bots() {
+        // Lifetimes 10, 20+, 30, 30 and 40+, where + marks a censored bot:
+        //
+        //   time  at risk  events  S(t)             Greenwood sum
+        //   10    5        1       4/5 = 0.8        1/(5*4) = 0.05
+        //   20    4        0       0.8              0.05
+        //   30    3        2       0.8 * 1/3        0.05 + 2/(3*1)
+        //   40    1        0       0.8 * 1/3        0.05 + 2/3
+        let lifetimes = [
+            lifetime(0, 10, Outcome::Depleted),
+            lifetime(1, 20, Outcome::Censored),
+            lifetime(2, 30, Outcome::Depleted),
+            lifetime(3, 30, Outcome::Killed),
+            lifetime(4, 40, Outcome::Censored),
+        ];
+        let steps = kaplan_meier(&lifetimes, SurvivalArgs::default());
+
+        let table: Vec<_> = steps
+            .iter()
+            .map(|step| (step.time, step.at_risk, step.events, step.censored))
+            .collect();
+        assert_eq!(
+            table,
+            vec![(10, 5, 1, 0), (20, 4, 0, 1), (30, 3, 2, 0), (40, 1, 0, 1)]
+        );
+
+        let survival = [0.8, 0.8, 0.8 / 3.0, 0.8 / 3.0];
+        for (step, survival) in steps.iter().zip(survival) {
+            assert_close(step.survival, survival);
+        }
+
+        // S(t) ± 1.96 S(t) sqrt(Greenwood sum), clamped to [0, 1].
+        assert_close(steps[0].lower, 0.8 - Z_95 * 0.8 * 0.05_f64.sqrt());
+        assert_close(steps[0].lower, 0.449391);
+        assert_close(steps[0].upper, 1.0);
+        assert_close(steps[1].lower, steps[0].lower);
+        assert_close(steps[2].lower, 0.0);
+        assert_close(steps[2].upper, 0.709128);
+        assert_close(steps[3].upper, steps[2].upper);
+
+        assert_eq!(median_lifetime(&steps), Some(30));
+    }
+
+    #[test]
+    fn censored_kills_are_not_events() {
+        let lifetimes = [
+            lifetime(0, 10, Outcome::Killed),
+            lifetime(1, 20, Outcome::Depleted),
+        ];
+        let steps = kaplan_meier(&lifetimes, SurvivalArgs { censor_kills: true });
+
+        assert_eq!(steps[0].events, 0);
+        assert_eq!(steps[0].censored, 1);
+        assert_close(steps[0].survival, 1.0);
+        // The last bot at risk is depleted: the curve drops to zero without a variance term.
+        assert_close(steps[1].survival, 0.0);
+        assert_close(steps[1].lower, 0.0);
+        assert_close(steps[1].upper, 0.0);
+    }
+
+    #[test]
+    fn no_median_while_most_bots_run() {
+        let lifetimes = [
+            lifetime(0, 10, Outcome::Depleted),
+            lifetime(1, 20, Outcome::Censored),
+            lifetime(2, 20, Outcome::Censored),
+        ];
+
+        assert_eq!(
+            median_lifetime(&kaplan_meier(&lifetimes, SurvivalArgs::default())),
+            None
+        );
+    }
+
+    #[test]
+    fn tells_kills_from_depletion() {
+        let record = |bot_id, energy, status| Record {
+            tick: 0,
+            bot_id,
+            energy,
+            data: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            rotation: 0.0,
+            status,
+            color: [0.0; 4],
+            r#type: ObjectType::Bot,
+        };
+        let frame = |tick, statuses: [(f64, BotStatus); 3]| Frame {
+            tick,
+            bots: statuses
+                .into_iter()
+                .enumerate()
+                .map(|(bot_id, (energy, status))| record(bot_id as u16, energy, status))
+                .collect(),
+            station: None,
+            target_station: None,
+        };
+
+        let mut lifetimes = Lifetimes::new(&[]);
+        lifetimes.accumulate(&frame(
+            2,
+            [
+                (5.0, BotStatus::Active),
+                (5.0, BotStatus::Active),
+                (5.0, BotStatus::Active),
+            ],
+        ));
+        lifetimes.accumulate(&frame(
+            4,
+            [
+                (0.5, BotStatus::Depleted),
+                (0.0, BotStatus::Depleted),
+                (5.0, BotStatus::Active),
+            ],
+        ));
+        lifetimes.accumulate(&frame(
+            6,
+            [
+                (0.5, BotStatus::Depleted),
+                (0.0, BotStatus::Depleted),
+                (4.0, BotStatus::Active),
+            ],
+        ));
+
+        let lifetimes: Vec<_> = lifetimes
+            .finish()
+            .iter()
+            .map(|lifetime| (lifetime.duration(), lifetime.outcome))
+            .collect();
+        assert_eq!(
+            lifetimes,
+            vec![
+                (2, Outcome::Depleted),
+                (2, Outcome::Killed),
+                (4, Outcome::Censored)
+            ]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Tables produced by metrics, printed to the terminal and written as CSV.
+
+use std::error::Error;
+use std::path::Path;
+
+use prettytable::{Cell, Row};
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub title: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Most rows printed to the terminal, the CSV always has all of them.
+    pub limit: Option<usize>,
+}
+
+impl Table {
+    pub fn new(title: &str, header: &[&str]) -> Self {
+        Table {
+            title: title.to_string(),
+            header: header.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+            limit: None,
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) -> &mut Self {
+        self.rows.push(row);
+        self
+    }
+
+    /// Only prints the first `rows` rows, for tables that can grow with the length of the log.
+    pub fn limit(&mut self, rows: usize) -> &mut Self {
+        self.limit = Some(rows);
+        self
+    }
+
+    pub fn print(&self) {
+        let shown = self
+            .limit
+            .map_or(self.rows.len(), |limit| limit.min(self.rows.len()));
+        let mut table = prettytable::Table::new();
+        table.add_row(Row::new(self.header.iter().map(|h| Cell::new(h)).collect()));
+        for row in &self.rows[..shown] {
+            table.add_row(Row::new(row.iter().map(|v| Cell::new(v)).collect()));
+        }
+
+        println!("{}", self.title);
+        table.printstd();
+        if shown < self.rows.len() {
+            println!(
+                "{} more rows not shown, the CSV written with --output-dir has all of them",
+                self.rows.len() - shown
+            );
+        }
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(&self.header)?;
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Conversion of simulator ticks to the time unit picked on the command line.
+
+pub use crate::constants::TICKS_PER_SECOND;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeUnit {
+    Ticks,
+    Seconds,
+    Minutes,
+    /// Minutes and seconds, plotted on an axis in seconds
+    #[value(name = "mm:ss")]
+    MinutesSeconds,
+}
+
+impl TimeUnit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ticks => "ticks",
+            Self::Seconds => "seconds",
+            Self::Minutes => "minutes",
+            Self::MinutesSeconds => "mm:ss",
+        }
+    }
+}
+
+// Time base shared by every time axis and table of a binary (not a doc comment, see `PlotArgs`).
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq)]
+pub struct TimeBase {
+    /// Unit of time axes and durations
+    #[arg(long = "time-unit", value_enum, default_value_t = TimeUnit::Ticks)]
+    pub unit: TimeUnit,
+    #[arg(long, default_value_t = TICKS_PER_SECOND)]
+    pub ticks_per_second: f64,
+}
+
+impl Default for TimeBase {
+    fn default() -> Self {
+        TimeBase {
+            unit: TimeUnit::Ticks,
+            ticks_per_second: TICKS_PER_SECOND,
+        }
+    }
+}
+
+impl TimeBase {
+    pub fn seconds(&self, ticks: f64) -> f64 {
+        ticks / self.ticks_per_second
+    }
+
+    pub fn ticks(&self, seconds: f64) -> f64 {
+        seconds * self.ticks_per_second
+    }
+
+    /// Position of `ticks` on a time axis.
+    pub fn value(&self, ticks: f64) -> f64 {
+        match self.unit {
+            TimeUnit::Ticks => ticks,
+            TimeUnit::Seconds | TimeUnit::MinutesSeconds => self.seconds(ticks),
+            TimeUnit::Minutes => self.seconds(ticks) / 60.0,
+        }
+    }
+
+    /// Whether time axes hold seconds that are labelled as minutes and seconds.
+    pub fn clock(&self) -> bool {
+        self.unit == TimeUnit::MinutesSeconds
+    }
+
+    /// `ticks` in the time unit, without the unit.
+    pub fn format(&self, ticks: f64) -> String {
+        match self.unit {
+            TimeUnit::Ticks => format!("{:.0}", ticks),
+            TimeUnit::Seconds => format!("{:.1}", self.value(ticks)),
+            TimeUnit::Minutes => format!("{:.2}", self.value(ticks)),
+            TimeUnit::MinutesSeconds => clock(self.seconds(ticks)),
+        }
+    }
+
+    /// Parses a time in the time unit (`m:ss` or seconds for mm:ss) into ticks.
+    pub fn parse(&self, time: &str) -> Result<f64, String> {
+        let invalid = |_| format!("invalid time in {}: {}", self.unit.name(), time);
+
+        let value = match (self.unit, time.split_once(':')) {
+            (TimeUnit::MinutesSeconds, Some((minutes, seconds))) => {
+                let minutes: f64 = minutes.trim().parse().map_err(invalid)?;
+                let seconds: f64 = seconds.trim().parse().map_err(invalid)?;
+                minutes * 60.0 + seconds
+            }
+            _ => time.trim().parse().map_err(invalid)?,
+        };
+
+        Ok(match self.unit {
+            TimeUnit::Ticks => value,
+            TimeUnit::Seconds | TimeUnit::MinutesSeconds => self.ticks(value),
+            TimeUnit::Minutes => self.ticks(value * 60.0),
+        })
+    }
+
+    /// Label of a time axis, e.g. "Time (seconds)".
+    pub fn label(&self) -> String {
+        format!("Time ({})", self.unit.name())
+    }
+}
+
+/// Formats seconds as `m:ss`; minutes don't wrap at the hour, 7500 seconds is `125:00`.
+pub fn clock(seconds: f64) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let seconds = seconds.abs().floor() as u64;
+    format!("{}{}:{:02}", sign, seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(unit: TimeUnit) -> TimeBase {
+        TimeBase {
+            unit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_each_unit_into_ticks() {
+        assert_eq!(base(TimeUnit::Ticks).parse("120"), Ok(120.0));
+        assert_eq!(base(TimeUnit::Seconds).parse(" 2.5 "), Ok(150.0));
+        assert_eq!(base(TimeUnit::Minutes).parse("1"), Ok(3600.0));
+        assert_eq!(base(TimeUnit::MinutesSeconds).parse("1:30"), Ok(5400.0));
+        assert_eq!(base(TimeUnit::MinutesSeconds).parse("90"), Ok(5400.0));
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        let mm_ss = base(TimeUnit::MinutesSeconds);
+        assert!(mm_ss.parse("1:").is_err());
+        assert!(mm_ss.parse("a:30").is_err());
+        assert!(mm_ss.parse("1:30:00").is_err());
+        assert!(base(TimeUnit::Seconds).parse("1:30").is_err());
+    }
+
+    #[test]
+    fn formats_minutes_and_seconds() {
+        let mm_ss = base(TimeUnit::MinutesSeconds);
+        assert_eq!(mm_ss.format(0.0), "0:00");
+        assert_eq!(mm_ss.format(59.0), "0:00");
+        assert_eq!(mm_ss.format(5400.0), "1:30");
+        assert_eq!(mm_ss.format(36000.0), "10:00");
+        assert_eq!(clock(-75.0), "-1:15");
+    }
+
+    #[test]
+    fn round_trips_minutes_and_seconds() {
+        let mm_ss = base(TimeUnit::MinutesSeconds);
+        for time in ["0:00", "0:59", "1:30", "12:05", "125:00"] {
+            let ticks = mm_ss.parse(time).unwrap();
+            assert_eq!(mm_ss.format(ticks), time);
+        }
+    }
+
+    #[test]
+    fn formats_other_units() {
+        assert_eq!(base(TimeUnit::Ticks).format(1234.4), "1234");
+        assert_eq!(base(TimeUnit::Seconds).format(90.0), "1.5");
+        assert_eq!(base(TimeUnit::Minutes).format(5400.0), "1.50");
+        assert_eq!(base(TimeUnit::Minutes).label(), "Time (minutes)");
+    }
+}
@@ -0,0 +1,463 @@
+//! Bot behaviour as a Markov chain over statuses: how often each status follows another, and how
+//! long bots stay in each status.
+//!
+//! Stays cut short by the start or end of the log, or by frames the bot is missing from, have an
+//! unknown length and are left out of the dwell times; their transitions still count.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::aggregate::{levels, plot, selected, status_color};
+use crate::metric::{Metric, Options, Output};
+use crate::plot::{Heatmap, Layer, Plot, Series};
+use crate::propagation::median;
+use crate::replay::{BotStatus, Frame};
+use crate::table::Table;
+use crate::time::TimeBase;
+
+/// Transitions between statuses and completed stays in each status, in ticks.
+#[derive(Debug, Clone, Default)]
+struct Chain {
+    transitions: BTreeMap<(BotStatus, BotStatus), usize>,
+    dwell: BTreeMap<BotStatus, Vec<u64>>,
+}
+
+impl Chain {
+    fn add(&mut self, other: &Chain) {
+        for (transition, count) in &other.transitions {
+            *self.transitions.entry(transition.clone()).or_default() += count;
+        }
+        for (status, stays) in &other.dwell {
+            self.dwell.entry(status.clone()).or_default().extend(stays);
+        }
+    }
+
+    /// Transitions leaving `from`.
+    fn leaving(&self, from: &BotStatus) -> usize {
+        self.transitions
+            .iter()
+            .filter(|((source, _), _)| source == from)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn probability(&self, from: &BotStatus, to: &BotStatus) -> Option<f64> {
+        let leaving = self.leaving(from);
+        let count = self
+            .transitions
+            .get(&(from.clone(), to.clone()))
+            .copied()
+            .unwrap_or(0);
+
+        (leaving > 0).then(|| count as f64 / leaving as f64)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Stay {
+    frame: usize,
+    status: BotStatus,
+    /// First tick of the stay, `None` when it started before the bot's first sample or a gap.
+    start: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct StatusTransitions {
+    bot_ids: Vec<u16>,
+    time: TimeBase,
+    frames: usize,
+    unknown: BTreeSet<BotStatus>,
+    stays: BTreeMap<u16, Stay>,
+    bots: BTreeMap<u16, Chain>,
+}
+
+impl StatusTransitions {
+    pub fn new(options: &Options) -> Self {
+        StatusTransitions {
+            bot_ids: options.bot_ids.clone(),
+            time: options.time,
+            ..Default::default()
+        }
+    }
+
+    fn swarm(&self) -> Chain {
+        let mut swarm = Chain::default();
+        for chain in self.bots.values() {
+            swarm.add(chain);
+        }
+
+        swarm
+    }
+
+    /// Statuses any selected bot was seen in, in the order of [`levels`].
+    fn statuses(&self, swarm: &Chain) -> Vec<BotStatus> {
+        let seen: BTreeSet<&BotStatus> = swarm
+            .transitions
+            .keys()
+            .flat_map(|(from, to)| [from, to])
+            .chain(swarm.dwell.keys())
+            .chain(self.stays.values().map(|stay| &stay.status))
+            .collect();
+
+        levels(&self.unknown)
+            .into_iter()
+            .filter(|status| seen.contains(status))
+            .collect()
+    }
+
+    fn matrix(&self, swarm: &Chain, statuses: &[BotStatus]) -> Table {
+        let header: Vec<String> = ["From".to_string(), "Transitions".to_string()]
+            .into_iter()
+            .chain(statuses.iter().map(|status| status.to_string()))
+            .collect();
+        let header: Vec<&str> = header.iter().map(String::as_str).collect();
+        let mut table = Table::new("Status Transition Probabilities (Swarm)", &header);
+
+        for from in statuses {
+            let mut row = vec![from.to_string(), swarm.leaving(from).to_string()];
+            row.extend(statuses.iter().map(|to| {
+                swarm
+                    .probability(from, to)
+                    .map_or("-".to_string(), |p| format!("{:.3}", p))
+            }));
+            table.push(row);
+        }
+
+        table
+    }
+
+    fn transitions(&self, swarm: &Chain) -> Table {
+        let mut table = Table::new(
+            "Status Transitions",
+            &["Bot", "From", "To", "Count", "Probability"],
+        );
+
+        let chains = self
+            .bots
+            .iter()
+            .map(|(bot_id, chain)| (bot_id.to_string(), chain))
+            .chain(std::iter::once(("Swarm".to_string(), swarm)));
+        for (bot, chain) in chains {
+            for ((from, to), count) in &chain.transitions {
+                table.push(vec![
+                    bot.clone(),
+                    from.to_string(),
+                    to.to_string(),
+                    count.to_string(),
+                    format!("{:.3}", chain.probability(from, to).unwrap_or(0.0)),
+                ]);
+            }
+        }
+
+        table
+    }
+
+    fn dwell(&self, swarm: &Chain, statuses: &[BotStatus]) -> Table {
+        let unit = self.time.unit.name();
+        let mean = format!("Mean ({})", unit);
+        let median_time = format!("Median ({})", unit);
+        let min = format!("Min ({})", unit);
+        let max = format!("Max ({})", unit);
+        let mut table = Table::new(
+            "Dwell Time per Status",
+            &["Bot", "Status", "Stays", &mean, &median_time, &min, &max],
+        );
+
+        let chains = self
+            .bots
+            .iter()
+            .map(|(bot_id, chain)| (bot_id.to_string(), chain))
+            .chain(std::iter::once(("Swarm".to_string(), swarm)));
+        for (bot, chain) in chains {
+            for status in statuses {
+                let Some(stays) = chain.dwell.get(status) else {
+                    continue;
+                };
+                let mut stays = stays.clone();
+                stays.sort();
+
+                let time = |ticks: f64| self.time.format(ticks);
+                let total: u64 = stays.iter().sum();
+                table.push(vec![
+                    bot.clone(),
+                    status.to_string(),
+                    stays.len().to_string(),
+                    time(total as f64 / stays.len() as f64),
+                    time(median(&stays).expect("Status without stays")),
+                    time(stays[0] as f64),
+                    time(stays[stays.len() - 1] as f64),
+                ]);
+            }
+        }
+
+        table
+    }
+
+    /// Transition probabilities as a heatmap, the first status on top.
+    fn heatmap(&self, swarm: &Chain, statuses: &[BotStatus]) -> Plot {
+        let n = statuses.len();
+        let mut plot = plot(self);
+        plot.legend = false;
+        plot.x.ticks = statuses
+            .iter()
+            .enumerate()
+            .map(|(i, status)| (i as f64, status.to_string()))
+            .collect();
+        plot.y.ticks = statuses
+            .iter()
+            .enumerate()
+            .map(|(i, status)| ((n - 1 - i) as f64, status.to_string()))
+            .collect();
+
+        let values = statuses
+            .iter()
+            .rev()
+            .flat_map(|from| {
+                statuses
+                    .iter()
+                    .map(move |to| swarm.probability(from, to).unwrap_or(f64::NAN))
+            })
+            .collect();
+
+        plot.x.min = Some(-0.5);
+        plot.x.max = Some(n as f64 - 0.5);
+        plot.y.min = Some(-0.5);
+        plot.y.max = Some(n as f64 - 0.5);
+        plot.heatmap(Heatmap {
+            x: (-0.5, n as f64 - 0.5),
+            y: (-0.5, n as f64 - 0.5),
+            columns: n,
+            rows: n,
+            values,
+            label: "Transition Probability".to_string(),
+        });
+
+        plot
+    }
+}
+
+impl Metric for StatusTransitions {
+    fn name(&self) -> &'static str {
+        "status-transitions"
+    }
+
+    fn title(&self) -> String {
+        "Status Transition Probabilities".to_string()
+    }
+
+    fn labels(&self) -> (String, String) {
+        ("To Status".to_string(), "From Status".to_string())
+    }
+
+    fn accumulate(&mut self, frame: &Frame) {
+        for record in &frame.bots {
+            if let BotStatus::Unknown(_) = record.status {
+                self.unknown.insert(record.status.clone());
+            }
+            if !selected(&self.bot_ids, record.bot_id) {
+                continue;
+            }
+
+            let chain = self.bots.entry(record.bot_id).or_default();
+            let stay = match self.stays.get(&record.bot_id) {
+                Some(stay) if stay.frame + 1 == self.frames => {
+                    if stay.status == record.status {
+                        Stay {
+                            frame: self.frames,
+                            ..stay.clone()
+                        }
+                    } else {
+                        *chain
+                            .transitions
+                            .entry((stay.status.clone(), record.status.clone()))
+                            .or_default() += 1;
+                        if let Some(start) = stay.start {
+                            chain
+                                .dwell
+                                .entry(stay.status.clone())
+                                .or_default()
+                                .push(frame.tick - start);
+                        }
+
+                        Stay {
+                            frame: self.frames,
+                            status: record.status.clone(),
+                            start: Some(frame.tick),
+                        }
+                    }
+                }
+                _ => Stay {
+                    frame: self.frames,
+                    status: record.status.clone(),
+                    start: None,
+                },
+            };
+            self.stays.insert(record.bot_id, stay);
+        }
+
+        self.frames += 1;
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Output> {
+        let swarm = self.swarm();
+        let statuses = self.statuses(&swarm);
+
+        let mut plot = Plot::new(
+            "Distribution of Dwell Times per Status",
+            (
+                &format!("Dwell Time ({})", self.time.unit.name()),
+                "Fraction of Stays",
+            ),
+        );
+        plot.x.clock = self.time.clock();
+        plot.y.min = Some(0.0);
+        plot.y.max = Some(1.0);
+
+        // Empirical distribution function of the stays in each status.
+        for (level, status) in levels(&self.unknown).iter().enumerate() {
+            let Some(stays) = swarm.dwell.get(status) else {
+                continue;
+            };
+            let mut stays = stays.clone();
+            stays.sort();
+
+            let mut series = Series {
+                name: status.to_string(),
+                x: vec![0.0],
+                y: vec![0.0],
+            };
+            for (i, ticks) in stays.iter().enumerate() {
+                let duration = self.time.value(*ticks as f64);
+                let before = i as f64 / stays.len() as f64;
+                series.x.extend([duration, duration]);
+                series
+                    .y
+                    .extend([before, (i + 1) as f64 / stays.len() as f64]);
+            }
+
+            plot.layers.push(Layer::Line {
+                series,
+                color: Some(status_color(level, status)),
+                width: 2.0,
+            });
+        }
+
+        vec![
+            Output::table(self.name(), self.matrix(&swarm, &statuses)),
+            Output::table(
+                &format!("{}-per-bot", self.name()),
+                self.transitions(&swarm),
+            ),
+            Output::table(
+                &format!("{}-dwell", self.name()),
+                self.dwell(&swarm, &statuses),
+            ),
+            Output::plot(self.name(), self.heatmap(&swarm, &statuses)),
+            Output::plot(&format!("{}-dwell", self.name()), plot),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{ObjectType, Record};
+
+    use BotStatus::{Active, DataTransfer, Trophallaxis};
+
+    /// Frames at ticks 0, 2, ... with the statuses of the bots in them.
+    fn transitions(frames: &[&[(u16, BotStatus)]]) -> StatusTransitions {
+        let mut transitions = StatusTransitions::default();
+        for (i, bots) in frames.iter().enumerate() {
+            let tick = i as u64 * 2;
+            transitions.accumulate(&Frame {
+                tick,
+                bots: bots
+                    .iter()
+                    .map(|(bot_id, status)| Record {
+                        tick,
+                        bot_id: *bot_id,
+                        energy: 100.0,
+                        data: Vec::new(),
+                        x: 0.0,
+                        y: 0.0,
+                        vel_x: 0.0,
+                        vel_y: 0.0,
+                        rotation: 0.0,
+                        status: status.clone(),
+                        color: [0.0; 4],
+                        r#type: ObjectType::Bot,
+                    })
+                    .collect(),
+                station: None,
+                target_station: None,
+            });
+        }
+        transitions
+    }
+
+    #[test]
+    fn counts_transitions_and_completed_stays() {
+        let transitions = transitions(&[
+            &[(0, Active), (1, Active)],
+            &[(0, Active), (1, Active)],
+            &[(0, Trophallaxis), (1, Active)],
+            &[(0, Trophallaxis), (1, Trophallaxis)],
+            &[(0, Active), (1, Active)],
+            &[(0, Active), (1, Active)],
+        ]);
+
+        let chain = &transitions.bots[&0];
+        assert_eq!(
+            chain.transitions,
+            BTreeMap::from([((Active, Trophallaxis), 1), ((Trophallaxis, Active), 1)])
+        );
+        // The first stay started before the log and the last one is still going.
+        assert_eq!(chain.dwell, BTreeMap::from([(Trophallaxis, vec![4])]));
+
+        let swarm = transitions.swarm();
+        assert_eq!(swarm.transitions[&(Active, Trophallaxis)], 2);
+        assert_eq!(swarm.dwell[&Trophallaxis], vec![4, 2]);
+        assert_eq!(swarm.probability(&Trophallaxis, &Active), Some(1.0));
+        assert_eq!(swarm.probability(&DataTransfer, &Active), None);
+    }
+
+    #[test]
+    fn leaves_out_stays_cut_by_a_gap() {
+        let transitions = transitions(&[
+            &[(0, Active)],
+            &[(0, Trophallaxis)],
+            &[(0, Trophallaxis)],
+            &[],
+            &[(0, Trophallaxis)],
+            &[(0, Active)],
+            &[(0, Trophallaxis)],
+        ]);
+
+        let chain = &transitions.bots[&0];
+        // Nothing is counted across the gap, the stay after it still ends in a transition.
+        assert_eq!(
+            chain.transitions,
+            BTreeMap::from([((Active, Trophallaxis), 2), ((Trophallaxis, Active), 1)])
+        );
+        assert_eq!(chain.dwell, BTreeMap::from([(Active, vec![2])]));
+    }
+
+    #[test]
+    fn censors_the_stay_at_the_first_sample() {
+        let transitions = transitions(&[
+            &[(0, DataTransfer)],
+            &[(0, DataTransfer)],
+            &[(0, Active)],
+            &[(0, DataTransfer)],
+            &[(0, DataTransfer)],
+            &[(0, Active)],
+        ]);
+
+        let chain = &transitions.bots[&0];
+        assert_eq!(chain.transitions[&(DataTransfer, Active)], 2);
+        assert_eq!(
+            chain.dwell,
+            BTreeMap::from([(Active, vec![2]), (DataTransfer, vec![4])])
+        );
+    }
+}